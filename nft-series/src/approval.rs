@@ -1,7 +1,11 @@
 use crate::*;
 use near_sdk::ext_contract;
 
-pub trait NonFungibleTokenCore {
+/// Maximum number of accounts that can be approved on a single token at once. This bounds the storage
+/// a token owner can make the token consume.
+pub const MAX_APPROVALS_PER_TOKEN: usize = 32;
+
+pub trait NonFungibleTokenApproval {
     //approve an account ID to transfer a token on your behalf
     fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>);
 
@@ -20,6 +24,8 @@ pub trait NonFungibleTokenCore {
     fn nft_revoke_all(&mut self, token_id: TokenId);
 }
 
+// only the generated `ext_non_fungible_approval_receiver` module is called, never the trait itself
+#[allow(dead_code)]
 #[ext_contract(ext_non_fungible_approval_receiver)]
 trait NonFungibleTokenApprovalsReceiver {
     //cross contract call to an external contract that is initiated during nft_approve
@@ -33,7 +39,7 @@ trait NonFungibleTokenApprovalsReceiver {
 }
 
 #[near_bindgen]
-impl NonFungibleTokenCore for Contract {
+impl NonFungibleTokenApproval for Contract {
     //allow a specific account ID to approve a token on your behalf
     #[payable]
     fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>) {
@@ -42,9 +48,10 @@ impl NonFungibleTokenCore for Contract {
             The user needs to attach enough to pay for storage on the contract
         */
         assert_at_least_one_yocto();
+        self.assert_operation_enabled(Operation::Approve);
 
         //get the token object from the token ID
-        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        let mut token = self.internal_get_token(&token_id);
 
         //make sure that the person calling the function is the owner of the token
         assert_eq!(
@@ -52,31 +59,35 @@ impl NonFungibleTokenCore for Contract {
             &token.owner_id,
             "Predecessor must be the token owner."
         );
+        //the owner can always move their own token, so approving them would only take up storage
+        require!(account_id != token.owner_id, "The token owner can't be approved on their own token");
+
+        //make sure a new approval doesn't push the token over the approval cap
+        if !token.approved_account_ids.contains_key(&account_id) {
+            assert!(
+                token.approved_account_ids.len() < MAX_APPROVALS_PER_TOKEN,
+                "A token can have at most {} approved accounts. Revoke one before approving another",
+                MAX_APPROVALS_PER_TOKEN
+            );
+        }
+
+        //measure the storage the approval actually takes up, so the caller pays for exactly that
+        let initial_storage_usage = env::storage_usage();
 
         //get the next approval ID if we need a new approval
         let approval_id: u64 = token.next_approval_id;
 
-        //check if the account has been approved already for this token
-        let is_new_approval = token
-            .approved_account_ids
-            //insert returns none if the key was not present.
-            .insert(account_id.clone(), approval_id)
-            //if the key was not present, .is_none() will return true so it is a new approval.
-            .is_none();
-
-        //if it was a new approval, we need to calculate how much storage is being used to add the account.
-        let storage_used = if is_new_approval {
-            bytes_for_approved_account_id(&account_id)
-        //if it was not a new approval, we used no storage.
-        } else {
-            0
-        };
+        //insert the approval, overwriting the approval ID if the account was approved already
+        token.approved_account_ids.insert(account_id.clone(), approval_id);
 
         //increment the token's next approval ID by 1
         token.next_approval_id += 1;
         //insert the token back into the tokens_by_id collection
         self.tokens_by_id.insert(&token_id, &token);
 
+        //re-approving an account rewrites the token at the same size, so only new approvals use storage
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+
         //refund any excess storage attached by the user. If the user didn't attach enough, panic.
         refund_deposit(storage_used);
 
@@ -98,7 +109,7 @@ impl NonFungibleTokenCore for Contract {
         approval_id: Option<u64>,
     ) -> bool {
         //get the token object from the token_id
-        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let token = self.internal_get_token(&token_id);
 
         //get the approval number for the passed in account ID
         let approval = token.approved_account_ids.get(&approved_account_id);
@@ -125,7 +136,7 @@ impl NonFungibleTokenCore for Contract {
         //assert that the user attached exactly 1 yoctoNEAR for security reasons
         assert_one_yocto();
         //get the token object using the passed in token_id
-        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        let mut token = self.internal_get_token(&token_id);
 
         //get the caller of the function and assert that they are the owner of the token
         let predecessor_account_id = env::predecessor_account_id();
@@ -148,7 +159,7 @@ impl NonFungibleTokenCore for Contract {
         assert_one_yocto();
 
         //get the token object from the passed in token ID
-        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        let mut token = self.internal_get_token(&token_id);
         //get the caller and make sure they are the owner of the tokens
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
@@ -164,3 +175,92 @@ impl NonFungibleTokenCore for Contract {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::accounts;
+
+    fn approve_many(contract: &mut Contract, token_id: &TokenId, count: usize) {
+        for i in 0..count {
+            set_context(accounts(1), STORAGE_DEPOSIT);
+            contract.nft_approve(token_id.clone(), format!("market{}.near", i).parse().unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn nft_approve_allows_up_to_the_cap() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        approve_many(&mut contract, &token_id, MAX_APPROVALS_PER_TOKEN);
+        // Re-approving an existing account doesn't count towards the cap
+        approve_many(&mut contract, &token_id, 1);
+
+        let token = contract.tokens_by_id.get(&token_id).unwrap();
+        assert_eq!(token.approved_account_ids.len(), MAX_APPROVALS_PER_TOKEN);
+    }
+
+    #[test]
+    #[should_panic(expected = "A token can have at most 32 approved accounts")]
+    fn nft_approve_rejects_one_over_the_cap() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        approve_many(&mut contract, &token_id, MAX_APPROVALS_PER_TOKEN + 1);
+    }
+
+    #[test]
+    fn nft_approve_refunds_everything_but_the_measured_storage() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_approve(token_id.clone(), accounts(2), None);
+        let cost = env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage);
+
+        assert!(cost > 0);
+        assert_eq!(transfers_to(&accounts(1)), vec![STORAGE_DEPOSIT - cost]);
+
+        // Re-approving the same account takes no new storage, so the whole deposit comes back
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id, accounts(2), None);
+        assert_eq!(transfers_to(&accounts(1)), vec![STORAGE_DEPOSIT]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The token owner can't be approved on their own token")]
+    fn nft_approve_rejects_the_token_owner() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id, accounts(1), None);
+    }
+
+    #[test]
+    fn malformed_approvee_is_rejected() {
+        //this is how the account_id argument of nft_approve is parsed, so a malformed ID never reaches the contract
+        let args = near_sdk::serde_json::json!({ "token_id": "1:1", "account_id": "Not A Valid Account" });
+
+        assert!(near_sdk::serde_json::from_value::<AccountId>(args["account_id"].clone()).is_err());
+        assert!(near_sdk::serde_json::from_value::<AccountId>("market.near".into()).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNDERFUNDED: Must attach")]
+    fn nft_approve_without_covering_storage_fails() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), 1);
+        contract.nft_approve(token_id, accounts(2), None);
+    }
+}
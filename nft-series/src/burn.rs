@@ -0,0 +1,448 @@
+use crate::*;
+use crate::storage::StorageRefunds;
+
+/// The most tokens `nft_batch_burn` burns in one call
+pub const MAX_BATCH_BURN: usize = 100;
+
+/// The most burned token IDs `recent_burns` remembers. Once full, each burn evicts the oldest ID.
+pub const MAX_RECENT_BURNS: u64 = 1_000;
+
+/// The IDs of the most recently burned tokens, kept in a ring buffer of `MAX_RECENT_BURNS` entries
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BurnLog {
+    token_ids: Vector<TokenId>,
+    //where the next burned ID goes once the buffer is full, which is also where the oldest ID is
+    next: u64,
+}
+
+impl BurnLog {
+    pub fn new(prefix: StorageKey) -> Self {
+        Self { token_ids: Vector::new(prefix), next: 0 }
+    }
+
+    pub fn record(&mut self, token_id: &TokenId) {
+        if self.token_ids.len() < MAX_RECENT_BURNS {
+            self.token_ids.push(token_id);
+        } else {
+            self.token_ids.replace(self.next, token_id);
+            self.next = (self.next + 1) % MAX_RECENT_BURNS;
+        }
+    }
+
+    /// The remembered IDs, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = TokenId> + '_ {
+        let len = self.token_ids.len();
+        (0..len).map(move |offset| self.token_ids.get((self.next + offset) % len).unwrap())
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Burn a token and refund its holder what they paid for it when it was minted. Only the series owner can call this.
+    /// Payments go straight to the series owner at mint time, so the series owner must attach the refund
+    /// (their escrow for the guarantee). Anything attached beyond the recorded price is returned to them.
+    /// The token's storage is refunded to whoever paid for it at mint time.
+    #[payable]
+    pub fn refund_and_revoke(&mut self, token_id: TokenId) {
+        let token = self.internal_get_token(&token_id);
+        let series = self.internal_get_series(token.series_id);
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == series.owner_id,
+            ContractError::Unauthorized.message("Only the series owner can refund and revoke a token")
+        );
+
+        let attached_deposit = env::attached_deposit();
+        require!(
+            attached_deposit >= token.paid_amount,
+            ContractError::Underfunded
+                .message(&format!("Must attach {} yoctoNEAR to refund the holder", token.paid_amount))
+        );
+
+        let token = self.internal_burn(&token_id, Some(caller.to_string()), None, None);
+
+        //the refunded payment no longer counts towards the series' revenue, which bottoms out at 0 rather than
+        //failing the refund if it somehow holds less than the token's price
+        let mut series = self.series_by_id.get(&token.series_id).unwrap();
+        series.revenue = series.revenue.saturating_sub(token.paid_amount);
+        self.series_by_id.insert(&token.series_id, &series);
+
+        //send the recorded price back to the holder and any excess back to the series owner
+        if token.paid_amount > 0 {
+            Promise::new(token.owner_id).transfer(token.paid_amount);
+        }
+        let excess = attached_deposit - token.paid_amount;
+        if excess > 0 {
+            Promise::new(caller).transfer(excess);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Revoke a badge, recording why in the `nft_burn` event. The series owner can revoke any of the series' badges
+    /// and holders can give up their own. The token's storage is refunded to whoever paid for it at mint time.
+    /// Like every other burn, it requires exactly 1 yoctoNEAR attached.
+    #[payable]
+    pub fn nft_revoke_badge(&mut self, token_id: TokenId, reason: RevokeReason, memo: Option<String>) {
+        assert_one_yocto();
+        let token = self.internal_get_token(&token_id);
+        let series = self.internal_get_series(token.series_id);
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == token.owner_id || caller == series.owner_id,
+            ContractError::Unauthorized.message("Only the holder or the series owner can revoke a badge")
+        );
+
+        //the holder revoking their own badge isn't acting on anyone's behalf
+        let authorized_id = Some(caller).filter(|caller| caller != &token.owner_id).map(|caller| caller.to_string());
+        self.internal_burn(&token_id, authorized_id, Some(reason), memo);
+    }
+
+    /// Burn several of the caller's own tokens at once, logged as a single `nft_burn` event.
+    /// If any of the tokens isn't owned by the caller nothing is burned. Each token's storage is refunded to
+    /// whoever paid for it at mint time. At most `MAX_BATCH_BURN` tokens can be burned at once.
+    /// Requires exactly 1 yoctoNEAR attached, as for transfers.
+    #[payable]
+    pub fn nft_batch_burn(&mut self, token_ids: Vec<TokenId>) {
+        assert_one_yocto();
+        require!(!token_ids.is_empty(), "Nothing to burn");
+        require!(
+            token_ids.len() <= MAX_BATCH_BURN,
+            format!("Can burn at most {} tokens at once", MAX_BATCH_BURN)
+        );
+        let caller = env::predecessor_account_id();
+        for token_id in token_ids.iter() {
+            require!(
+                self.internal_get_token(token_id).owner_id == caller,
+                ContractError::Unauthorized.message(&format!("Token {} isn't owned by the caller", token_id))
+            );
+        }
+
+        let mut refunds = StorageRefunds::default();
+        for token_id in token_ids.iter() {
+            self.internal_remove_token(token_id, &mut refunds);
+        }
+        self.internal_pay_storage_refunds(refunds);
+
+        EventLogVariant::NftBurn(vec![NftBurnLog {
+            owner_id: caller.to_string(),
+            authorized_id: None,
+            token_ids,
+            reason: Some(RevokeReason::Voluntary),
+            memo: None,
+        }]).emit();
+    }
+
+    /// List the IDs of recently burned tokens, oldest first, so indexers can purge them. Only the latest
+    /// `MAX_RECENT_BURNS` burns are kept, older ones are evicted as new tokens are burned.
+    pub fn recent_burns(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<TokenId> {
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+        self.recent_burns
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    /// Revoke up to `limit` (at most `MAX_BATCH_BURN`) of an account's badges, e.g. ones it was granted by fraud,
+    /// logged as a single `nft_burn` event with the `admin_cleanup` reason. Call it again until it returns 0:
+    /// it returns how many of the account's tokens are left. Each token's storage is refunded to whoever paid for it
+    /// at mint time. Only the contract owner can call this.
+    pub fn revoke_all_for_account(&mut self, account_id: AccountId, limit: u64) -> u64 {
+        self.assert_contract_owner();
+
+        let token_ids: Vec<TokenId> = self
+            .tokens_per_owner
+            .get(&account_id)
+            .map(|tokens| tokens.iter().take((limit as usize).min(MAX_BATCH_BURN)).collect())
+            .unwrap_or_default();
+        let mut refunds = StorageRefunds::default();
+        for token_id in token_ids.iter() {
+            self.internal_remove_token(token_id, &mut refunds);
+        }
+        self.internal_pay_storage_refunds(refunds);
+
+        if !token_ids.is_empty() {
+            EventLogVariant::NftBurn(vec![NftBurnLog {
+                owner_id: account_id.to_string(),
+                authorized_id: Some(env::predecessor_account_id().to_string()),
+                token_ids,
+                reason: Some(RevokeReason::AdminCleanup),
+                memo: None,
+            }]).emit();
+        }
+
+        self.tokens_per_owner.get(&account_id).map_or(0, |tokens| tokens.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::{accounts, get_logs};
+    use near_sdk::testing_env;
+
+    const PRICE: Balance = 5 * STORAGE_DEPOSIT;
+
+    fn setup_with_paid_token() -> (Contract, TokenId) {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                price: Some(U128(PRICE)),
+                ..series_spec(1, sample_metadata("Paid"))
+            },
+            None,
+        );
+
+        set_context(accounts(1), PRICE + STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        (contract, "1:1".to_string())
+    }
+
+    #[test]
+    fn refund_and_revoke_refunds_the_paid_price_and_burns() {
+        let (mut contract, token_id) = setup_with_paid_token();
+
+        let charge = contract.tokens_by_id.get(&token_id).unwrap().storage_charge.unwrap();
+        assert_eq!(charge.account_id, accounts(1));
+
+        set_context(owner(), PRICE);
+        contract.refund_and_revoke(token_id.clone());
+
+        //the holder paid for the token's storage when minting it, so that comes back too
+        let transfers = transfers_to(&accounts(1));
+        assert_eq!(transfers.len(), 2);
+        assert!(transfers[0] > 0 && transfers[0] <= Balance::from(charge.bytes) * env::storage_byte_cost());
+        assert_eq!(transfers[1], PRICE);
+        assert!(contract.tokens_by_id.get(&token_id).is_none());
+        assert_eq!(contract.nft_supply_for_series(1).0, 0);
+        assert!(contract.tokens_per_owner.get(&accounts(1)).is_none());
+    }
+
+    #[test]
+    fn minting_after_a_burn_does_not_reuse_the_token_id() {
+        let (mut contract, token_id) = setup_with_paid_token();
+        set_context(owner(), PRICE);
+        contract.refund_and_revoke(token_id);
+
+        set_context(accounts(2), PRICE + STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(2), None, None, None, None);
+
+        assert_eq!(contract.token_ids_for_series(1, None, None), vec!["1:2".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the series owner can refund and revoke a token")]
+    fn only_series_owner_can_refund_and_revoke() {
+        let (mut contract, token_id) = setup_with_paid_token();
+
+        set_context(accounts(1), PRICE);
+        contract.refund_and_revoke(token_id);
+    }
+
+    #[test]
+    fn refund_and_revoke_reduces_series_revenue() {
+        let (mut contract, token_id) = setup_with_paid_token();
+        assert_eq!(contract.series_revenue(1).0, PRICE);
+
+        set_context(owner(), PRICE);
+        contract.refund_and_revoke(token_id);
+
+        assert_eq!(contract.series_revenue(1).0, 0);
+    }
+
+    #[test]
+    fn refund_and_revoke_never_takes_revenue_below_zero() {
+        let (mut contract, token_id) = setup_with_paid_token();
+        let mut series = contract.series_by_id.get(&1).unwrap();
+        series.revenue = PRICE / 2;
+        contract.series_by_id.insert(&1, &series);
+
+        set_context(owner(), PRICE);
+        contract.refund_and_revoke(token_id);
+
+        assert_eq!(contract.series_revenue(1).0, 0);
+    }
+
+    #[test]
+    fn revoke_reason_appears_in_the_burn_event() {
+        let reasons = [
+            (RevokeReason::Voluntary, "voluntary"),
+            (RevokeReason::Fraud, "fraud"),
+            (RevokeReason::Expired, "expired"),
+            (RevokeReason::Superseded, "superseded"),
+            (RevokeReason::AdminCleanup, "admin_cleanup"),
+        ];
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 2);
+
+        for (reason, expected) in reasons {
+            let token_id = mint_to(&mut contract, 2, accounts(1));
+            set_context(owner(), 1);
+            contract.nft_revoke_badge(token_id.clone(), reason, None);
+
+            let log = near_sdk::test_utils::get_logs().pop().unwrap();
+            assert!(log.contains(r#""event":"nft_burn""#));
+            assert!(log.contains(&format!(r#""reason":"{}""#, expected)), "{}", log);
+            assert!(contract.tokens_by_id.get(&token_id).is_none());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the holder or the series owner can revoke a badge")]
+    fn others_cannot_revoke_a_badge() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 2);
+        let token_id = mint_to(&mut contract, 2, accounts(1));
+
+        set_context(accounts(2), 1);
+        contract.nft_revoke_badge(token_id, RevokeReason::Fraud, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn revoking_a_badge_requires_one_yocto() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(owner(), 0);
+        contract.nft_revoke_badge(token_id, RevokeReason::Fraud, None);
+    }
+
+    #[test]
+    fn batch_burn_burns_every_owned_token_in_one_event() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_ids: Vec<TokenId> = (0..3).map(|_| mint_to(&mut contract, 1, accounts(1))).collect();
+
+        set_context(accounts(1), 1);
+        contract.nft_batch_burn(token_ids.clone());
+
+        assert_eq!(contract.nft_supply_for_owner(accounts(1)).0, 0);
+        let logs = get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains(r#""token_ids":["1:1","1:2","1:3"]"#));
+        //the contract owner minted the tokens, so their storage goes back to it in a single transfer
+        assert_eq!(transfers_to(&owner()).len(), 1);
+        assert!(transfers_to(&accounts(1)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn batch_burn_requires_one_yocto() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), 0);
+        contract.nft_batch_burn(vec![token_id]);
+    }
+
+    #[test]
+    fn revoking_a_badge_refunds_its_storage_to_the_minter() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        let charge = contract.tokens_by_id.get(&token_id).unwrap().storage_charge.unwrap();
+
+        set_context(accounts(1), 1);
+        contract.nft_revoke_badge(token_id, RevokeReason::Voluntary, None);
+
+        let refund = transfers_to(&owner());
+        assert_eq!(refund.len(), 1);
+        assert!(refund[0] > 0 && refund[0] <= Balance::from(charge.bytes) * env::storage_byte_cost());
+        assert!(transfers_to(&accounts(1)).is_empty());
+    }
+
+    #[test]
+    fn batch_burn_with_a_token_of_someone_else_burns_nothing() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let mine = mint_to(&mut contract, 1, accounts(1));
+        let theirs = mint_to(&mut contract, 1, accounts(2));
+
+        set_context(accounts(1), 1);
+        let burn = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_batch_burn(vec![mine.clone(), theirs.clone()]);
+        }));
+
+        assert!(burn.unwrap_err().downcast_ref::<String>().unwrap().contains("ERR_UNAUTHORIZED"));
+        assert!(contract.nft_token(mine).is_some());
+        assert!(contract.nft_token(theirs).is_some());
+    }
+
+    #[test]
+    fn burning_the_only_token_frees_the_owner_set() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        // A new mocked context resets the storage usage, so the revoke's context carries it over from the mint
+        set_context(owner(), STORAGE_DEPOSIT);
+        let storage_before_mint = env::storage_usage();
+        let token_id = contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        let storage_after_mint = env::storage_usage();
+        let mut context = set_context(owner(), 1);
+        testing_env!(context.storage_usage(storage_after_mint).build());
+        contract.nft_revoke_badge(token_id.clone(), RevokeReason::AdminCleanup, None);
+
+        assert!(contract.tokens_per_owner.get(&accounts(1)).is_none());
+        assert!(env::storage_usage() < storage_after_mint);
+        // All that's left is the burned ID in `recent_burns`: the 40 byte record overhead, its key and the ID
+        let key_len = StorageKey::RecentBurns.try_to_vec().unwrap().len() + 8;
+        let burn_log_entry = 40 + key_len + token_id.try_to_vec().unwrap().len();
+        assert_eq!(env::storage_usage(), storage_before_mint + burn_log_entry as u64);
+    }
+
+    #[test]
+    fn burned_ids_are_listed_oldest_first() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_ids: Vec<TokenId> = (0..3).map(|_| mint_to(&mut contract, 1, accounts(1))).collect();
+
+        set_context(accounts(1), 1);
+        contract.nft_batch_burn(vec![token_ids[1].clone(), token_ids[0].clone()]);
+        set_context(owner(), 1);
+        contract.nft_revoke_badge(token_ids[2].clone(), RevokeReason::Fraud, None);
+
+        assert_eq!(contract.recent_burns(None, None), vec!["1:2", "1:1", "1:3"]);
+        assert_eq!(contract.recent_burns(Some(U128(1)), Some(1)), vec!["1:1"]);
+    }
+
+    #[test]
+    fn the_burn_log_evicts_the_oldest_ids_once_full() {
+        let mut burn_log = BurnLog::new(StorageKey::RecentBurns);
+        for edition in 1..=MAX_RECENT_BURNS + 2 {
+            burn_log.record(&token_id_for_edition(1, edition));
+        }
+
+        let burned: Vec<TokenId> = burn_log.iter().collect();
+        assert_eq!(burned.len() as u64, MAX_RECENT_BURNS);
+        assert_eq!(burned[0], "1:3");
+        assert_eq!(burned.last().unwrap(), &token_id_for_edition(1, MAX_RECENT_BURNS + 2));
+    }
+
+    #[test]
+    fn revoking_everything_for_an_account_takes_repeated_calls() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        for _ in 0..5 {
+            mint_to(&mut contract, 1, accounts(1));
+        }
+        let kept = mint_to(&mut contract, 1, accounts(2));
+
+        set_context(owner(), 0);
+        assert_eq!(contract.revoke_all_for_account(accounts(1), 2), 3);
+        assert!(get_logs()[0].contains(r#""reason":"admin_cleanup""#));
+        assert_eq!(contract.revoke_all_for_account(accounts(1), 2), 1);
+        assert_eq!(contract.revoke_all_for_account(accounts(1), 2), 0);
+        assert_eq!(contract.revoke_all_for_account(accounts(1), 2), 0);
+
+        assert_eq!(contract.nft_supply_for_owner(accounts(1)).0, 0);
+        assert!(contract.nft_token(kept).is_some());
+    }
+}
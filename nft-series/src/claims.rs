@@ -0,0 +1,140 @@
+use crate::*;
+
+/// How many tokens of a series an allowlisted account may claim for itself, and how many it already has
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClaimAllowance {
+    pub allowed: u64,
+    pub claimed: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Allow `account_id` to claim up to `allowed` tokens of the series for itself with `nft_claim`, no minter needed.
+    /// Claims already made still count, so lowering the allowance below them leaves nothing to claim.
+    /// Only the series owner can call this and they attach the storage for new entries (the excess is refunded).
+    #[payable]
+    pub fn set_claim_allowance(&mut self, series_id: SeriesId, account_id: AccountId, allowed: u64) {
+        let series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        let initial_storage_usage = env::storage_usage();
+
+        let key = (series_id, account_id);
+        let mut allowance = self.claim_allowances.get(&key).unwrap_or_default();
+        allowance.allowed = allowed;
+        if allowance == ClaimAllowance::default() {
+            self.claim_allowances.remove(&key);
+        } else {
+            self.claim_allowances.insert(&key, &allowance);
+        }
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Claim a token of the series for the caller, using up one of their claims. Storage (and the price, if the
+    /// series has one) is paid from the attached deposit like any other mint. Fails once the series' claim deadline
+    /// has passed. Returns the minted token ID.
+    #[payable]
+    pub fn nft_claim(&mut self, series_id: SeriesId) -> TokenId {
+        let claimer = env::predecessor_account_id();
+        require!(!self.internal_get_series(series_id).claim_window_closed(), "Claim window closed");
+        require!(
+            self.remaining_claims(claimer.clone(), series_id) > 0,
+            ContractError::Unauthorized.message("No claims left for this series")
+        );
+
+        let key = (series_id, claimer.clone());
+        let mut allowance = self.claim_allowances.get(&key).unwrap();
+        allowance.claimed += 1;
+        self.claim_allowances.insert(&key, &allowance);
+
+        self.internal_mint_many(series_id, vec![(claimer, None)], None, true, MintOptions::default()).pop().unwrap()
+    }
+
+    /// How many more tokens of the series `account_id` can claim: its allowance minus what it already claimed,
+    /// and at most one if the series allows one token per account and none if the account already holds it.
+    /// Accounts without an allowance, or whose claim window closed, get 0.
+    pub fn remaining_claims(&self, account_id: AccountId, series_id: SeriesId) -> u64 {
+        let allowance = match self.claim_allowances.get(&(series_id, account_id.clone())) {
+            Some(allowance) => allowance,
+            None => return 0,
+        };
+        let remaining = allowance.allowed.saturating_sub(allowance.claimed);
+
+        match self.series_by_id.get(&series_id) {
+            Some(series) if series.claim_window_closed() => 0,
+            Some(series) if series.unique_per_account => {
+                if self.internal_holds_series_token(&account_id, series_id) {
+                    0
+                } else {
+                    remaining.min(1)
+                }
+            }
+            Some(_) => remaining,
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn claiming_uses_up_the_allowance() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_claim_allowance(1, accounts(1), 2);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        let token_id = contract.nft_claim(1);
+
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+        assert_eq!(contract.remaining_claims(accounts(1), 1), 1);
+        assert_eq!(contract.remaining_claims(accounts(2), 1), 0);
+    }
+
+    #[test]
+    fn unique_series_leave_at_most_one_claim() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_unique_per_account(1, true);
+        contract.set_claim_allowance(1, accounts(1), 3);
+        assert_eq!(contract.remaining_claims(accounts(1), 1), 1);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_claim(1);
+
+        assert_eq!(contract.remaining_claims(accounts(1), 1), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: No claims left for this series")]
+    fn claiming_without_an_allowance_fails() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_claim(1);
+    }
+
+    #[test]
+    fn claims_close_after_the_deadline() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_claim_allowance(1, accounts(1), 1);
+        contract.set_series_claim_deadline(1, Some(1_000));
+
+        let mut context = set_context(accounts(1), STORAGE_DEPOSIT);
+        testing_env!(context.block_timestamp(1_001 * 1_000_000).build());
+        let claim = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_claim(1);
+        }));
+
+        assert!(claim.unwrap_err().downcast_ref::<String>().unwrap().contains("Claim window closed"));
+        assert_eq!(contract.remaining_claims(accounts(1), 1), 0);
+        assert_eq!(contract.nft_supply_for_series(1).0, 0);
+    }
+}
@@ -1,18 +1,74 @@
 use crate::*;
 use crate::nft_core::NonFungibleTokenCore;
 
+/// The most accounts `holders_check` accepts in one call, to keep the view within gas limits
+pub const MAX_HOLDERS_CHECK_ACCOUNTS: usize = 100;
 
 /// Struct to return in views to query for specific data related to a series
 #[derive(BorshDeserialize, BorshSerialize, Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct JsonSeries {
-    series_id: u64,
+    pub series_id: u64,
     // Metadata including title, num copies etc.. that all tokens will derive from
-    metadata: TokenMetadata,
+    pub metadata: TokenMetadata,
     // Royalty used for all tokens in the collection
-    royalty: Option<HashMap<AccountId, u32>>,
+    pub royalty: Option<HashMap<AccountId, u32>>,
     // Owner of the collection
-    owner_id: AccountId,
+    pub owner_id: AccountId,
+    // Description of the badge program
+    pub description: Option<String>,
+    // Link to more information about the badge program
+    pub external_url: Option<String>,
+    // Whether the series shows up in public listings
+    pub visible: bool,
+    // Whether holders can trade the series' tokens with each other
+    pub transferable: bool,
+    // Whether an account can hold at most one token of the series
+    pub unique_per_account: bool,
+    // Whether the series' pricing is frozen for good
+    pub locked: bool,
+    // Whether the series is only handed out for free
+    pub claim_only: bool,
+    // Banner/collection image for the program, separate from the per-token `metadata.media`
+    pub collection_media: Option<String>,
+    // When allowlisted claims close, Unix epoch in milliseconds
+    pub claim_deadline: Option<u64>,
+    // Whether anyone can mint the series, not just approved minters
+    pub public_mint_enabled: bool,
+    // Who pays for the storage of the series' mints
+    pub storage_payer: StoragePayer,
+}
+
+/// A summary of the badges an account holds in one series, for profile pages
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesBadgeSummary {
+    pub series_id: u64,
+    pub series_title: Option<String>,
+    // How many of the series' tokens the account holds
+    pub count: u64,
+    // When the most recent of those tokens was minted, Unix epoch in milliseconds
+    pub latest_minted_at: u64,
+}
+
+/// What an account can manage, for creator dashboards
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ManagedSeries {
+    // The series the account owns
+    pub series_ids: Vec<SeriesId>,
+    // Whether the account can create new series (see `can_create_series`)
+    pub can_create_series: bool,
+}
+
+/// How many tokens were ever minted and burned on the contract, and how many exist now
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SupplyBreakdown {
+    pub minted: u64,
+    pub burned: u64,
+    // minted - burned, the same as `nft_total_supply`
+    pub active: u64,
 }
 
 #[near_bindgen]
@@ -23,21 +79,53 @@ impl Contract {
         U128(self.tokens_by_id.len() as u128)
     }
 
-    //Query for nft tokens on the contract regardless of the owner using pagination
+    /// Split the supply into every token ever minted, the ones burned since and the ones still around, for dashboards.
+    /// Counted as tokens are minted and burned, so it's cheap to call.
+    pub fn supply_breakdown(&self) -> SupplyBreakdown {
+        SupplyBreakdown {
+            minted: self.tokens_minted,
+            burned: self.tokens_burned,
+            active: self.tokens_minted - self.tokens_burned,
+        }
+    }
+
+    //Query for nft tokens on the contract regardless of the owner using pagination.
+    //tokens are listed in the order of the map's key vector, so for a given state every from_index/limit always
+    //returns the same page and paging through the whole set visits each token exactly once. A burn moves the
+    //last token into the burned one's slot, so indexers should restart from 0 if tokens are burned mid-way.
     pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
+        let token_ids = self.tokens_by_id.keys_as_vector();
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
-        let start = u128::from(from_index.unwrap_or(U128(0)));
+        let start = u128::from(from_index.unwrap_or(U128(0))).min(token_ids.len() as u128) as u64;
+        //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+        let end = start.saturating_add(limit.unwrap_or(50)).min(token_ids.len());
+
+        //read only the requested slots and map the token IDs which are strings into Json Tokens
+        (start..end)
+            .filter_map(|index| token_ids.get(index))
+            .map(|token_id| self.nft_token(token_id).unwrap())
+            .collect()
+    }
+
+    /// List the tokens minted between `from_ts` and `to_ts` (inclusive, Unix epoch in milliseconds), e.g. for
+    /// "badges minted this week". `from_index` and `limit` (default 50) page through the matching tokens.
+    /// Tokens aren't indexed by time, so every call scans all tokens up to the end of the page: on large contracts
+    /// narrow pages may still run out of view gas, and indexers should prefer the `nft_mint` events.
+    pub fn tokens_by_time_range(
+        &self,
+        from_ts: u64,
+        to_ts: u64,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
 
-        //iterate through each token using an iterator
         self.tokens_by_id
-            .keys()
-            //skip to the index we specified in the start variable
-            .skip(start as usize)
-            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .iter()
+            .filter(|(_, token)| (from_ts..=to_ts).contains(&token.minted_at))
+            .skip(start)
             .take(limit.unwrap_or(50) as usize)
-            //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
-            //since we turned the keys into an iterator, we need to turn it back into a vector to return
+            .map(|(token_id, _)| self.nft_token(token_id).unwrap())
             .collect()
     }
 
@@ -55,12 +143,13 @@ impl Contract {
         }
     }
 
-    //Query for all the tokens for an owner
+    //Query for all the tokens for an owner, optionally only the ones in the given series
     pub fn nft_tokens_for_owner(
         &self,
         account_id: AccountId,
         from_index: Option<U128>,
         limit: Option<u64>,
+        series_id: Option<SeriesId>,
     ) -> Vec<JsonToken> {
         //get the set of tokens for the passed in owner
         let tokens_for_owner_set = self.tokens_per_owner.get(&account_id);
@@ -72,12 +161,24 @@ impl Contract {
             return vec![];
         };
 
+        //when filtering by series, walk the smaller of the owner's and the series' sets and look the IDs up in the other one
+        let token_ids: Box<dyn Iterator<Item = TokenId>> =
+            match series_id.map(|series_id| self.series_by_id.get(&series_id)) {
+                None => Box::new(tokens.iter()),
+                Some(None) => return vec![],
+                Some(Some(series)) if series.tokens.len() < tokens.len() => Box::new(
+                    series.tokens.iter().filter(|token_id| tokens.contains(token_id)).collect::<Vec<_>>().into_iter(),
+                ),
+                Some(Some(series)) => Box::new(
+                    tokens.iter().filter(|token_id| series.tokens.contains(token_id)).collect::<Vec<_>>().into_iter(),
+                ),
+            };
+
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
         //iterate through the keys vector
-        tokens
-            .iter()
+        token_ids
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
@@ -93,42 +194,81 @@ impl Contract {
         self.series_by_id.len()
     }
 
-    // Paginate through all the series on the contract and return the a vector of JsonSeries
+    // Paginate through all the visible series on the contract and return the a vector of JsonSeries.
+    // Hidden series are left out: a view can't tell who is calling, so it can't show them only to their owners.
     pub fn get_series(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonSeries> {
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
         //iterate through each series using an iterator
         self.series_by_id
-            .keys()
+            .iter()
+            //leave out hidden series before paginating so pages stay consistent
+            .filter(|(_, series)| series.visible)
+            .map(|(series_id, _)| series_id)
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
             //we'll map the series IDs which are strings into Json Series
-            .map(|series_id| self.get_series_details(series_id.clone()).unwrap())
+            .map(|series_id| self.get_series_details(series_id).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
-    // get info for a specific series
-    pub fn get_series_details(&self, id: u64) -> Option<JsonSeries> {
-        //get the series from the map
-        let series = self.series_by_id.get(&id);
-        //if there is some series, we'll return the series
-        if let Some(series) = series {
-            Some(JsonSeries {
-                series_id: id,
-                metadata: series.metadata,
-                royalty: series.royalty,
-                owner_id: series.owner_id,
+    /// List the series anyone can mint right now, e.g. for a "mint now" page: visible, open to public minting,
+    /// not paused and with copies left. Empty while minting is disabled contract-wide.
+    pub fn open_series(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonSeries> {
+        if !self.is_operation_enabled(Operation::Mint) {
+            return vec![];
+        }
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.series_by_id
+            .iter()
+            //filter before paginating so pages stay consistent, like `get_series`
+            .filter(|(_, series)| {
+                series.visible
+                    && series.public_mint_enabled
+                    && !series.minting_paused
+                    && series.metadata.copies.is_none_or(|copies| series.tokens.len() < copies)
             })
-        } else {
-            //if there isn't a series, we'll return None
-            None
+            .map(|(series_id, _)| series_id)
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|series_id| self.get_series_details(series_id).unwrap())
+            .collect()
+    }
+
+    /// List the series `account_id` owns and whether it can create new ones, e.g. for a creator dashboard
+    pub fn managed_series(&self, account_id: AccountId) -> ManagedSeries {
+        ManagedSeries {
+            series_ids: self.series_per_owner.get(&account_id).map(|series| series.to_vec()).unwrap_or_default(),
+            can_create_series: self.internal_can_create_series(&account_id),
         }
     }
 
+    /// List every series `owner_id` owns, hidden ones included, e.g. so a creator can review a program before
+    /// launch. Hiding a series only keeps it out of public listings: contract state is public, so this isn't private.
+    pub fn get_series_for_owner(&self, owner_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonSeries> {
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+        let Some(series_ids) = self.series_per_owner.get(&owner_id) else {
+            return vec![];
+        };
+
+        series_ids
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .filter_map(|series_id| self.internal_json_series(series_id))
+            .collect()
+    }
+
+    // get info for a specific series. Hidden series aren't returned until they're made visible
+    pub fn get_series_details(&self, id: u64) -> Option<JsonSeries> {
+        self.internal_json_series(id).filter(|series| series.visible)
+    }
+
     //get the total supply of NFTs on a current series
     pub fn nft_supply_for_series(&self, id: u64) -> U128 {
         //get the series
@@ -172,4 +312,612 @@ impl Contract {
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
+
+    /// Paginate through the token IDs within a given series without resolving their metadata
+    pub fn token_ids_for_series(
+        &self,
+        series_id: u64,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenId> {
+        // Get the series and its tokens
+        let tokens = if let Some(series) = self.series_by_id.get(&series_id) {
+            series.tokens
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the token IDs in the order they were added to the series
+        tokens
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    /// The series' price in NEAR as a human-readable string (e.g. "1.5"), computed exactly from yoctoNEAR.
+    /// Returns `None` for free series and unknown series.
+    pub fn series_price_near(&self, series_id: u64) -> Option<String> {
+        self.series_by_id
+            .get(&series_id)
+            .and_then(|series| series.price)
+            .map(format_near)
+    }
+
+    /// Net revenue of a series: everything paid to mint its tokens minus what was refunded
+    pub fn series_revenue(&self, series_id: u64) -> U128 {
+        U128(self.series_by_id.get(&series_id).map(|series| series.revenue).unwrap_or(0))
+    }
+
+    /// Summarize an account's badges grouped by series, ordered by series ID and paginated over the series
+    pub fn profile_badges(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<SeriesBadgeSummary> {
+        let tokens = if let Some(tokens) = self.tokens_per_owner.get(&account_id) {
+            tokens
+        } else {
+            return vec![];
+        };
+
+        //group the account's tokens by series, keeping the count and the latest mint time
+        let mut by_series: std::collections::BTreeMap<SeriesId, (u64, u64)> = Default::default();
+        for token_id in tokens.iter() {
+            let token = self.tokens_by_id.get(&token_id).unwrap();
+            let entry = by_series.entry(token.series_id).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 = entry.1.max(token.minted_at);
+        }
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        by_series
+            .into_iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .map(|(series_id, (count, latest_minted_at))| SeriesBadgeSummary {
+                series_id,
+                series_title: self.series_by_id.get(&series_id).and_then(|series| series.metadata.title),
+                count,
+                latest_minted_at,
+            })
+            .collect()
+    }
+
+    /// Get the 1-based edition number of a token within its series, e.g. to show "Edition 7 of 100"
+    pub fn token_edition(&self, token_id: TokenId) -> Option<u64> {
+        self.tokens_by_id.get(&token_id).map(|token| token.edition)
+    }
+
+    /// Get the series a token belongs to without loading the whole `JsonToken`
+    pub fn token_series_id(&self, token_id: TokenId) -> Option<SeriesId> {
+        self.tokens_by_id.get(&token_id).map(|token| token.series_id)
+    }
+
+    /// The ID the next mint of the series will get, e.g. for UIs that render the token before it exists.
+    /// Editions are never reused, so this is `${series_id}:${editions minted so far + 1}` even after burns.
+    pub fn next_token_id(&self, series_id: SeriesId) -> TokenId {
+        let series = self.internal_get_series(series_id);
+        token_id_for_edition(series_id, series.editions_minted + 1)
+    }
+
+    /// Look up a token by the external ID it was minted with (see `nft_mint`)
+    pub fn token_by_external_id(&self, external_id: String) -> Option<JsonToken> {
+        self.tokens_by_external_id
+            .get(&external_id)
+            .and_then(|token_id| self.nft_token(token_id))
+    }
+
+    /// List the owners a token has had, oldest first, with when each became the owner.
+    /// Only the latest `MAX_PROVENANCE_ENTRIES` owners are kept.
+    pub fn token_provenance(&self, token_id: TokenId) -> Vec<ProvenanceEntry> {
+        self.provenance_by_token.get(&token_id).unwrap_or_default()
+    }
+
+    /// Count the distinct accounts holding at least one token of the series. An account holding several editions
+    /// counts once. Tokens held since before this count was introduced aren't included until `index_series_holdings`
+    /// has run over them.
+    pub fn series_unique_owners(&self, series_id: SeriesId) -> u64 {
+        self.unique_owners_by_series.get(&series_id).unwrap_or(0)
+    }
+
+    /// Check which of the given accounts hold a token of the series, answering in the same order as `accounts`.
+    /// Handy for validating a guest list in one call. At most `MAX_HOLDERS_CHECK_ACCOUNTS` accounts can be checked at once.
+    pub fn holders_check(&self, series_id: SeriesId, accounts: Vec<AccountId>) -> Vec<bool> {
+        require!(
+            accounts.len() <= MAX_HOLDERS_CHECK_ACCOUNTS,
+            format!("Can check at most {} accounts at once", MAX_HOLDERS_CHECK_ACCOUNTS)
+        );
+
+        accounts
+            .iter()
+            .map(|account_id| self.internal_holds_series_token(account_id, series_id))
+            .collect()
+    }
+
+    /// List the series `account_id` could mint to themselves right now: minting isn't paused, copies are left,
+    /// the account may mint the series (it's open to the public, they're an approved minter or have claims left),
+    /// they're an eligible receiver, and a one-per-account badge isn't already held.
+    /// Pages over all series, so a page can come back shorter than `limit`.
+    pub fn claimable_series(&self, account_id: AccountId, from_index: Option<U128>, limit: Option<u64>) -> Vec<SeriesId> {
+        if !self.is_operation_enabled(Operation::Mint) {
+            return vec![];
+        }
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.series_by_id
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .filter(|(series_id, series)| {
+                !series.minting_paused
+                    && series.metadata.copies.is_none_or(|copies| series.tokens.len() < copies)
+                    && (self.internal_may_mint(series, &account_id, false)
+                        || self.remaining_claims(account_id.clone(), *series_id) > 0)
+                    && series.is_eligible_receiver(&account_id)
+                    && !(series.unique_per_account && self.internal_holds_series_token(&account_id, *series_id))
+            })
+            .map(|(series_id, _)| series_id)
+            .collect()
+    }
+}
+
+impl Contract {
+    //the JSON form of a series, whether it's visible or not
+    pub(crate) fn internal_json_series(&self, id: SeriesId) -> Option<JsonSeries> {
+        //get the series from the map
+        let series = self.series_by_id.get(&id);
+        //if there is some series, we'll return the series
+        if let Some(series) = series {
+            Some(JsonSeries {
+                series_id: id,
+                metadata: series.metadata,
+                royalty: series.royalty,
+                owner_id: series.owner_id,
+                description: series.description,
+                external_url: series.external_url,
+                visible: series.visible,
+                transferable: series.transferable,
+                unique_per_account: series.unique_per_account,
+                locked: series.locked,
+                claim_only: series.claim_only,
+                collection_media: series.collection_media,
+                claim_deadline: series.claim_deadline,
+                public_mint_enabled: series.public_mint_enabled,
+                storage_payer: series.storage_payer,
+            })
+        } else {
+            //if there isn't a series, we'll return None
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn token_ids_for_series_paginates_in_mint_order() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        for _ in 0..3 {
+            mint_to(&mut contract, 1, accounts(1));
+        }
+
+        let first_page = contract.token_ids_for_series(1, None, Some(2));
+        let second_page = contract.token_ids_for_series(1, Some(U128(2)), Some(2));
+
+        assert_eq!(first_page, vec!["1:1".to_string(), "1:2".to_string()]);
+        assert_eq!(second_page, vec!["1:3".to_string()]);
+        assert!(contract.token_ids_for_series(2, None, None).is_empty());
+    }
+
+    #[test]
+    fn hidden_series_are_left_out_of_listings_and_details() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        contract.set_series_visible(2, false);
+
+        let public: Vec<u64> = contract.get_series(None, None).iter().map(|s| s.series_id).collect();
+
+        assert_eq!(public, vec![1]);
+        assert!(contract.get_series_details(2).is_none());
+        contract.set_series_visible(2, true);
+        assert_eq!(contract.get_series_details(2).unwrap().series_id, 2);
+    }
+
+    #[test]
+    fn owners_see_their_hidden_series() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        contract.set_series_visible(2, false);
+
+        let owned: Vec<(u64, bool)> =
+            contract.get_series_for_owner(owner(), None, None).iter().map(|s| (s.series_id, s.visible)).collect();
+
+        assert_eq!(owned, vec![(1, true), (2, false)]);
+        assert_eq!(contract.get_series_for_owner(owner(), Some(U128(1)), Some(1)).len(), 1);
+        assert!(contract.get_series_for_owner(accounts(1), None, None).is_empty());
+    }
+
+    #[test]
+    fn series_revenue_sums_paid_mints() {
+        let mut contract = setup_contract();
+        let price = 2 * STORAGE_DEPOSIT;
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                price: Some(U128(price)),
+                ..series_spec(1, sample_metadata("Paid"))
+            },
+            None,
+        );
+
+        for buyer in 1..4 {
+            set_context(accounts(buyer), price + STORAGE_DEPOSIT);
+            contract.nft_mint(U64(1), accounts(buyer), None, None, None, None);
+        }
+        // The series owner mints for free and doesn't add revenue
+        mint_to(&mut contract, 1, owner());
+
+        assert_eq!(contract.series_revenue(1).0, 3 * price);
+        assert_eq!(contract.series_revenue(2).0, 0);
+    }
+
+    #[test]
+    fn profile_badges_groups_tokens_by_series() {
+        let mut contract = setup_contract();
+        for series_id in 1..4 {
+            create_sample_series(&mut contract, series_id);
+        }
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 2, accounts(1));
+        mint_to(&mut contract, 3, accounts(1));
+        mint_to(&mut contract, 3, accounts(1));
+        mint_to(&mut contract, 3, accounts(2));
+
+        let summary = contract.profile_badges(accounts(1), None, None);
+        let counts: Vec<(u64, u64)> = summary.iter().map(|s| (s.series_id, s.count)).collect();
+
+        assert_eq!(counts, vec![(1, 1), (2, 1), (3, 2)]);
+        assert_eq!(summary[0].series_title.as_deref(), Some("Badge"));
+        assert_eq!(contract.profile_badges(accounts(1), Some(U128(2)), Some(5)).len(), 1);
+        assert!(contract.profile_badges(accounts(3), None, None).is_empty());
+    }
+
+    #[test]
+    fn holders_check_answers_in_input_order() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 2, accounts(2));
+        mint_to(&mut contract, 1, accounts(3));
+
+        let checked = contract.holders_check(1, vec![accounts(3), accounts(2), accounts(1), accounts(4)]);
+
+        assert_eq!(checked, vec![true, false, true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can check at most 100 accounts at once")]
+    fn holders_check_caps_the_account_list() {
+        let contract = setup_contract();
+        contract.holders_check(1, vec![accounts(1); MAX_HOLDERS_CHECK_ACCOUNTS + 1]);
+    }
+
+    #[test]
+    fn token_edition_follows_mint_order() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_ids: Vec<TokenId> = (0..3).map(|_| mint_to(&mut contract, 1, accounts(1))).collect();
+
+        let editions: Vec<Option<u64>> = token_ids.into_iter().map(|token_id| contract.token_edition(token_id)).collect();
+
+        assert_eq!(editions, vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(contract.token_edition("1:4".to_string()), None);
+    }
+
+    #[test]
+    fn token_series_id_reads_the_tokens_series() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        let token_id = mint_to(&mut contract, 2, accounts(1));
+
+        assert_eq!(contract.token_series_id(token_id), Some(2));
+        assert_eq!(contract.token_series_id("1:1".to_string()), None);
+    }
+
+    #[test]
+    fn paging_nft_tokens_visits_every_token_once() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        for series_id in [1, 2, 1, 2, 1, 2, 1] {
+            mint_to(&mut contract, series_id, accounts(1));
+        }
+        set_context(owner(), 1);
+        contract.nft_revoke_badge("1:2".to_string(), RevokeReason::AdminCleanup, None);
+
+        let mut visited = vec![];
+        let mut from_index = 0;
+        loop {
+            let page = contract.nft_tokens(Some(U128(from_index)), Some(2));
+            if page.is_empty() {
+                break;
+            }
+            from_index += page.len() as u128;
+            visited.extend(page.into_iter().map(|token| token.token_id));
+        }
+
+        let mut expected: Vec<TokenId> = contract.tokens_by_id.keys().collect();
+        assert_eq!(visited.len(), 6);
+        assert_eq!(visited, expected);
+        visited.sort();
+        visited.dedup();
+        expected.sort();
+        assert_eq!(visited, expected);
+        assert!(!visited.contains(&"1:2".to_string()));
+    }
+
+    #[test]
+    fn mint_and_admin_transfer_are_recorded_in_the_provenance() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        let mut context = set_context(owner(), 0);
+        testing_env!(context.block_timestamp(5_000_000).build());
+        contract.admin_transfer(token_id.clone(), accounts(2), "rotate service account".to_string());
+
+        let provenance = contract.token_provenance(token_id);
+        assert_eq!(provenance.len(), 2);
+        assert_eq!(provenance[0], ProvenanceEntry { owner_id: accounts(1), timestamp: 0 });
+        assert_eq!(provenance[1], ProvenanceEntry { owner_id: accounts(2), timestamp: 5 });
+    }
+
+    #[test]
+    fn provenance_keeps_only_the_latest_owners() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(owner(), 0);
+        for transfer in 0..MAX_PROVENANCE_ENTRIES {
+            let new_owner_id = if transfer % 2 == 0 { accounts(2) } else { accounts(1) };
+            contract.admin_transfer(token_id.clone(), new_owner_id, "rotate service account".to_string());
+        }
+
+        let provenance = contract.token_provenance(token_id);
+        assert_eq!(provenance.len(), MAX_PROVENANCE_ENTRIES);
+        assert_eq!(provenance[0].owner_id, accounts(2));
+    }
+
+    #[test]
+    fn series_price_is_formatted_in_near() {
+        let mut contract = setup_contract();
+        for (series_id, price) in [(1, Some(STORAGE_DEPOSIT)), (2, Some(STORAGE_DEPOSIT * 3 / 2)), (3, None), (4, Some(1))] {
+            set_context(owner(), STORAGE_DEPOSIT);
+            contract.create_series_from_spec(
+                SeriesSpec {
+                    price: price.map(U128),
+                    ..series_spec(series_id, sample_metadata("Badge"))
+                },
+                None,
+            );
+        }
+
+        assert_eq!(contract.series_price_near(1).as_deref(), Some("1"));
+        assert_eq!(contract.series_price_near(2).as_deref(), Some("1.5"));
+        assert_eq!(contract.series_price_near(3), None);
+        assert_eq!(contract.series_price_near(4).as_deref(), Some("0.000000000000000000000001"));
+        assert_eq!(format_near(0), "0");
+    }
+
+    #[test]
+    fn tokens_for_owner_can_be_filtered_by_series() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        for series_id in [1, 2, 1, 2, 2] {
+            mint_to(&mut contract, series_id, accounts(1));
+        }
+        mint_to(&mut contract, 2, accounts(2));
+
+        let token_ids = |tokens: Vec<JsonToken>| tokens.into_iter().map(|token| token.token_id).collect::<Vec<_>>();
+        assert_eq!(token_ids(contract.nft_tokens_for_owner(accounts(1), None, None, Some(1))), vec!["1:1", "1:2"]);
+        assert_eq!(token_ids(contract.nft_tokens_for_owner(accounts(1), Some(U128(1)), Some(1), Some(2))), vec!["2:2"]);
+        assert_eq!(contract.nft_tokens_for_owner(accounts(1), None, None, None).len(), 5);
+        assert!(contract.nft_tokens_for_owner(accounts(1), None, None, Some(3)).is_empty());
+    }
+
+    #[test]
+    fn claimable_series_leaves_out_sold_out_and_claimed_series() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mut limited = sample_metadata("Limited");
+        limited.copies = Some(1);
+        contract.create_series_from_spec(series_spec(1, limited), None);
+        create_sample_series(&mut contract, 2);
+        create_sample_series(&mut contract, 3);
+        contract.set_series_unique_per_account(3, true);
+        contract.add_approved_minter(accounts(1));
+
+        mint_to(&mut contract, 1, accounts(2));
+        mint_to(&mut contract, 3, accounts(1));
+
+        assert_eq!(contract.claimable_series(accounts(1), None, None), vec![2]);
+        assert!(contract.claimable_series(accounts(3), None, None).is_empty());
+    }
+
+    #[test]
+    fn claimable_series_follows_the_mint_rules() {
+        let mut contract = setup_contract();
+        for series_id in 1..=3 {
+            create_sample_series(&mut contract, series_id);
+        }
+        contract.update_series_price(1, Some(STORAGE_DEPOSIT));
+        contract.update_series_price(2, Some(STORAGE_DEPOSIT));
+        contract.set_series_public_mint(2, true);
+        contract.set_series_public_mint(3, true);
+        contract.add_eligible_receivers(3, vec![accounts(2)]);
+
+        assert_eq!(contract.claimable_series(accounts(1), None, None), vec![2]);
+        assert_eq!(contract.claimable_series(accounts(2), None, None), vec![2, 3]);
+        assert_eq!(contract.claimable_series(owner(), None, None), vec![1, 2]);
+
+        contract.set_operation_enabled(Operation::Mint, false);
+        assert!(contract.claimable_series(accounts(2), None, None).is_empty());
+    }
+
+    #[test]
+    fn open_series_leaves_out_sold_out_paused_and_closed_series() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mut limited = sample_metadata("Limited");
+        limited.copies = Some(1);
+        contract.create_series_from_spec(series_spec(1, limited), None);
+        for series_id in 2..=4 {
+            create_sample_series(&mut contract, series_id);
+            contract.set_series_public_mint(series_id, true);
+        }
+        contract.set_series_public_mint(1, true);
+        contract.set_series_minting_paused(3, true);
+        contract.set_series_public_mint(4, false);
+        create_sample_series(&mut contract, 5);
+
+        mint_to(&mut contract, 1, accounts(1));
+
+        let open: Vec<u64> = contract.open_series(None, None).into_iter().map(|series| series.series_id).collect();
+        assert_eq!(open, vec![2]);
+
+        set_context(owner(), 0);
+        contract.set_operation_enabled(Operation::Mint, false);
+        assert!(contract.open_series(None, None).is_empty());
+    }
+
+    #[test]
+    fn managed_series_lists_only_the_creators_own_series() {
+        let mut contract = setup_contract();
+        contract.add_approved_creator(accounts(1));
+        create_sample_series(&mut contract, 1);
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(series_spec(2, sample_metadata("Theirs")), None);
+        contract.create_series_from_spec(series_spec(3, sample_metadata("Handed over")), None);
+        set_context(accounts(1), 0);
+        contract.transfer_series_control(3, accounts(2), false);
+
+        assert_eq!(contract.managed_series(accounts(1)), ManagedSeries { series_ids: vec![2], can_create_series: true });
+        assert_eq!(contract.managed_series(accounts(2)), ManagedSeries { series_ids: vec![3], can_create_series: false });
+        assert_eq!(contract.managed_series(owner()).series_ids, vec![1]);
+    }
+
+    #[test]
+    fn unique_owners_count_each_holder_once() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let first = mint_to(&mut contract, 1, accounts(1));
+        let second = mint_to(&mut contract, 1, accounts(1));
+        assert_eq!(contract.series_unique_owners(1), 1);
+
+        mint_to(&mut contract, 1, accounts(2));
+        assert_eq!(contract.series_unique_owners(1), 2);
+
+        set_context(accounts(1), 1);
+        contract.nft_revoke_badge(first, RevokeReason::Voluntary, None);
+        assert_eq!(contract.series_unique_owners(1), 2);
+        contract.nft_revoke_badge(second, RevokeReason::Voluntary, None);
+        assert_eq!(contract.series_unique_owners(1), 1);
+        assert_eq!(contract.series_unique_owners(2), 0);
+    }
+
+    #[test]
+    fn tokens_can_be_looked_up_by_external_id() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let token_id = contract.nft_mint(U64(1), accounts(1), None, None, Some("row-42".to_string()), None);
+
+        let token = contract.token_by_external_id("row-42".to_string()).unwrap();
+        assert_eq!(token.token_id, token_id);
+        assert_eq!(token.external_id, Some("row-42".to_string()));
+        assert!(contract.token_by_external_id("row-43".to_string()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "External ID row-42 is already used by another token")]
+    fn duplicate_external_ids_are_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, Some("row-42".to_string()), None);
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(2), None, None, Some("row-42".to_string()), None);
+    }
+
+    #[test]
+    fn next_token_id_predicts_the_minted_id() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 3);
+        let first = mint_to(&mut contract, 3, accounts(1));
+        set_context(accounts(1), 1);
+        contract.nft_batch_burn(vec![first]);
+
+        let predicted = contract.next_token_id(3);
+        let minted = mint_to(&mut contract, 3, accounts(2));
+
+        assert_eq!(predicted, "3:2");
+        assert_eq!(predicted, minted);
+    }
+
+    #[test]
+    fn supply_breakdown_counts_burned_tokens_separately() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        set_context(accounts(1), 1);
+        contract.nft_batch_burn(vec![token_id]);
+
+        assert_eq!(contract.supply_breakdown(), SupplyBreakdown { minted: 1, burned: 1, active: 0 });
+        assert_eq!(contract.nft_total_supply().0, 0);
+    }
+
+    #[test]
+    fn tokens_by_time_range_filters_on_mint_time() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let mut minted = vec![];
+        for day in 1..=4u64 {
+            let mut context = set_context(owner(), STORAGE_DEPOSIT);
+            testing_env!(context.block_timestamp(day * 86_400_000 * 1_000_000).build());
+            minted.push(contract.nft_mint(U64(1), accounts(1), None, None, None, None));
+        }
+
+        let in_range: Vec<TokenId> = contract
+            .tokens_by_time_range(2 * 86_400_000, 3 * 86_400_000, None, None)
+            .into_iter()
+            .map(|token| token.token_id)
+            .collect();
+        assert_eq!(in_range, minted[1..3].to_vec());
+
+        let second_page = contract.tokens_by_time_range(2 * 86_400_000, 3 * 86_400_000, Some(U128(1)), Some(5));
+        assert_eq!(second_page.len(), 1);
+        assert_eq!(second_page[0].token_id, minted[2]);
+    }
 }
@@ -0,0 +1,160 @@
+use crate::*;
+
+/// The ways a call can fail. Every panic for one of these starts with the error's code, e.g.
+/// `ERR_SERIES_NOT_FOUND: Not a series`, so integrators can match the prefix instead of the wording after it.
+/// Codes are stable; the text after the colon is for humans and may change.
+///
+/// | Code                   | Meaning                                                                  |
+/// |------------------------|--------------------------------------------------------------------------|
+/// | `ERR_UNAUTHORIZED`     | The caller isn't allowed to do this (not the owner, minter or approved)  |
+/// | `ERR_SERIES_NOT_FOUND` | No series with the given ID                                              |
+/// | `ERR_TOKEN_NOT_FOUND`  | No token with the given ID                                               |
+/// | `ERR_SOLD_OUT`         | The series has minted all its copies                                     |
+/// | `ERR_SOULBOUND`        | The token's series isn't transferable or the receiver isn't allowed     |
+/// | `ERR_UNDERFUNDED`      | The attached deposit doesn't cover the price or storage                  |
+/// | `ERR_PAUSED`           | This class of operation is disabled, or minting of the series is paused  |
+/// | `ERR_ALREADY_HOLDS`    | The receiver already holds a token of a one-per-account series           |
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractError {
+    Unauthorized,
+    SeriesNotFound,
+    TokenNotFound,
+    SoldOut,
+    Soulbound,
+    Underfunded,
+    Paused,
+    AlreadyHolds,
+}
+
+impl ContractError {
+    /// The stable code every panic message for this error starts with
+    pub const fn code(&self) -> &'static str {
+        match self {
+            ContractError::Unauthorized => "ERR_UNAUTHORIZED",
+            ContractError::SeriesNotFound => "ERR_SERIES_NOT_FOUND",
+            ContractError::TokenNotFound => "ERR_TOKEN_NOT_FOUND",
+            ContractError::SoldOut => "ERR_SOLD_OUT",
+            ContractError::Soulbound => "ERR_SOULBOUND",
+            ContractError::Underfunded => "ERR_UNDERFUNDED",
+            ContractError::Paused => "ERR_PAUSED",
+            ContractError::AlreadyHolds => "ERR_ALREADY_HOLDS",
+        }
+    }
+
+    /// The full panic message: the code followed by a human readable detail
+    pub fn message(&self, detail: &str) -> String {
+        format!("{}: {}", self.code(), detail)
+    }
+
+    /// Abort the call with this error. Like `require!`, it panics normally in debug builds so unit tests can catch it.
+    pub(crate) fn panic(&self, detail: &str) -> ! {
+        let message = self.message(detail);
+        if cfg!(debug_assertions) {
+            panic!("{}", message)
+        } else {
+            env::panic_str(&message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn messages_start_with_the_code() {
+        assert_eq!(ContractError::SoldOut.message("No copies left"), "ERR_SOLD_OUT: No copies left");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: Only the series owner can update the series")]
+    fn updating_someone_elses_series_is_unauthorized() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(accounts(1), 0);
+        contract.set_series_visible(1, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SERIES_NOT_FOUND: Not a series")]
+    fn minting_an_unknown_series_fails() {
+        let mut contract = setup_contract();
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(7), accounts(1), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOKEN_NOT_FOUND: No token")]
+    fn moving_an_unknown_token_fails() {
+        let mut contract = setup_contract();
+
+        contract.admin_transfer("1:1".to_string(), accounts(1), "rotation".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SOLD_OUT: ")]
+    fn minting_past_the_copy_limit_fails() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mut metadata = sample_metadata("Limited");
+        metadata.copies = Some(1);
+        contract.create_series_from_spec(series_spec(1, metadata), None);
+
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SOULBOUND: Transfer not allowed to this address")]
+    fn transferring_outside_the_allowlist_fails() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), 1);
+        contract.nft_transfer(accounts(2), token_id, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNDERFUNDED: Need to attach at least enough to cover price")]
+    fn minting_without_covering_the_price_fails() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                price: Some(U128(STORAGE_DEPOSIT)),
+                ..series_spec(1, sample_metadata("Paid"))
+            },
+            None,
+        );
+
+        set_context(accounts(1), STORAGE_DEPOSIT / 2);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Series minting paused")]
+    fn minting_a_paused_series_fails() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_minting_paused(1, true);
+
+        mint_to(&mut contract, 1, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_HOLDS: Account already holds this badge")]
+    fn minting_a_second_unique_badge_fails() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_unique_per_account(1, true);
+
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(1));
+    }
+}
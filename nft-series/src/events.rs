@@ -1,9 +1,13 @@
 use std::fmt;
 
+use near_sdk::env;
 use near_sdk::serde::{Deserialize, Serialize};
 
+use crate::{NFTContractMetadata, SeriesId, BADGES_EVENTS_VERSION, BADGES_STANDARD_NAME, NFT_EVENTS_VERSION, NFT_STANDARD_NAME};
+
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be an NftMint, an NftTransfer, an NftBurn, an NftMetadataUpdate, a ContractMetadataUpdate,
+/// an AdminTransfer or a TreasuryUpdate.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +16,31 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+    NftMetadataUpdate(Vec<NftMetadataUpdateLog>),
+    ContractMetadataUpdate(Vec<ContractMetadataUpdateLog>),
+    AdminTransfer(Vec<AdminTransferLog>),
+    TreasuryUpdate(Vec<TreasuryUpdateLog>),
+}
+
+impl EventLogVariant {
+    /// Log the event as a single NEP-297 `EVENT_JSON` line with every entry in its data.
+    /// Batch operations pass all their entries at once so they emit one event rather than one per token.
+    /// NEP-171 events are logged under `nep171`; this contract's own events are logged under `devhub_badges`.
+    pub fn emit(self) {
+        let (standard, version) = match self {
+            EventLogVariant::AdminTransfer(_) | EventLogVariant::TreasuryUpdate(_) => {
+                (BADGES_STANDARD_NAME, BADGES_EVENTS_VERSION)
+            }
+            _ => (NFT_STANDARD_NAME, NFT_EVENTS_VERSION),
+        };
+        let event_log = EventLog {
+            standard: standard.to_string(),
+            version: version.to_string(),
+            event: self,
+        };
+        env::log_str(&event_log.to_string());
+    }
 }
 
 /// Interface to capture data about an event
@@ -78,6 +107,105 @@ pub struct NftTransferLog {
     pub memo: Option<String>,
 }
 
+/// Why a badge was revoked, reported in burn events for compliance
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[serde(crate = "near_sdk::serde")]
+pub enum RevokeReason {
+    Voluntary,
+    Fraud,
+    Expired,
+    Superseded,
+    AdminCleanup,
+}
+
+/// An event log to capture token burning
+///
+/// Arguments
+/// * `owner_id`: owner of tokens to burn
+/// * `authorized_id`: approved account to burn, if applicable
+/// * `token_ids`: ["1","2"]
+/// * `reason`: why the tokens were revoked, if applicable
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    pub owner_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<String>,
+
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<RevokeReason>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture token metadata changes
+///
+/// Arguments
+/// * `series_id`: the series whose metadata changed, if the whole series was updated
+/// * `token_ids`: ["1:1", "1:2"]
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataUpdateLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series_id: Option<SeriesId>,
+
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture contract-level metadata and settings changes
+///
+/// Arguments
+/// * `metadata`: the new contract metadata, when it's what changed
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractMetadataUpdateLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<NFTContractMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture the contract owner moving a token regardless of the transfer allowlist
+///
+/// Arguments
+/// * `authorized_id`: the contract owner that moved the token
+/// * `old_owner_id`: "service-v1.near"
+/// * `new_owner_id`: "service-v2.near"
+/// * `token_ids`: ["1:1"]
+/// * `reason`: why the token was moved
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AdminTransferLog {
+    pub authorized_id: String,
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub token_ids: Vec<String>,
+    pub reason: String,
+}
+
+/// An event log to capture the contract owner changing where fees are sent
+///
+/// Arguments
+/// * `old_treasury_id`: "devhub.near"
+/// * `new_treasury_id`: "treasury.devhub.near"
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryUpdateLog {
+    pub old_treasury_id: String,
+    pub new_treasury_id: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +263,36 @@ mod tests {
         };
         assert_eq!(expected, log.to_string());
     }
+
+    #[test]
+    fn nep_format_burn() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_burn","data":[{"owner_id":"owner.near","authorized_id":"issuer.near","token_ids":["1:1"]}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                owner_id: "owner.near".to_string(),
+                authorized_id: Some("issuer.near".to_string()),
+                token_ids: vec!["1:1".to_string()],
+                reason: None,
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_metadata_update() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"nft_metadata_update","data":[{"series_id":1,"token_ids":["1:1"]}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.1.0".to_string(),
+            event: EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+                series_id: Some(1),
+                token_ids: vec!["1:1".to_string()],
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
 }
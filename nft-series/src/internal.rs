@@ -1,10 +1,109 @@
 use crate::*;
+use crate::interop::GAS_FOR_ON_BADGE_MINTED;
+use crate::storage::StorageRefunds;
 use near_sdk::CryptoHash;
+use std::collections::HashSet;
 use std::mem::size_of;
 
-//convert the royalty percentage and amount to pay into a payout (U128)
+//the optional parts of a mint that only `nft_mint` passes: the caller-scoped request ID that makes retries safe,
+//the token's external ID and its credential JSON
+#[derive(Default)]
+pub(crate) struct MintOptions {
+    pub request_key: Option<String>,
+    pub external_id: Option<String>,
+    pub credential: Option<String>,
+}
+
+//convert the royalty percentage and amount to pay into a payout (U128), rounded down.
+//the amount is split into whole multiples of 10000 and the rest so any balance is exact without overflowing
 pub(crate) fn royalty_to_payout(royalty_percentage: u32, amount_to_pay: Balance) -> U128 {
-    U128(royalty_percentage as u128 * amount_to_pay / 10_000u128)
+    let royalty_percentage = royalty_percentage as u128;
+    U128(amount_to_pay / 10_000 * royalty_percentage + amount_to_pay % 10_000 * royalty_percentage / 10_000)
+}
+
+//the ID of a series' token with the given edition number: `${series_id}:${edition}`
+pub(crate) fn token_id_for_edition(series_id: SeriesId, edition: u64) -> TokenId {
+    format!("{}:{}", series_id, edition)
+}
+
+//how many accounts a payout can go to: every royalty recipient plus the token owner, and the series owner if the
+//series has a royalty since rounding dust may be paid to them. Each account is counted once.
+//whether there's dust depends on the balance, so the series owner is counted even for balances that split evenly.
+pub(crate) fn payout_receiver_count(
+    royalty: Option<&HashMap<AccountId, u32>>,
+    owner_id: &AccountId,
+    series_owner_id: &AccountId,
+) -> u32 {
+    let recipients = royalty.map_or(0, |royalty| royalty.keys().filter(|account_id| *account_id != owner_id).count());
+    let dust_receiver = royalty.is_some_and(|royalty| series_owner_id != owner_id && !royalty.contains_key(series_owner_id));
+    recipients as u32 + 1 + dust_receiver as u32
+}
+
+//split `balance` between the royalty recipients and the token owner, who gets 100% - total perpetual royalties.
+//every share is rounded down, so whatever is left unallocated (the dust) is paid to the series owner.
+//this guarantees the payout always sums to exactly `balance`.
+pub(crate) fn royalty_payout(
+    royalty: Option<&HashMap<AccountId, u32>>,
+    owner_id: AccountId,
+    series_owner_id: AccountId,
+    balance: Balance,
+    max_len_payout: u32,
+) -> Payout {
+    //make sure we're not paying out to too many people (GAS limits this)
+    assert!(
+        payout_receiver_count(royalty, &owner_id, &series_owner_id) <= max_len_payout,
+        "Market cannot payout to that many receivers"
+    );
+
+    let mut payout = HashMap::new();
+    //keep track of the total perpetual royalties
+    let mut total_perpetual = 0;
+
+    if let Some(royalty) = royalty {
+        //shares are validated when set, but series stored before that validation existed may still hold a map
+        //over 100%. Refuse to pay out rather than hand the recipients more than the balance
+        let total_bps: u64 = royalty.values().map(|percentage| u64::from(*percentage)).sum();
+        require!(
+            total_bps <= 10_000,
+            format!(
+                "Stored royalty shares add up to {} basis points, more than 10000. The series owner must fix the royalty before this token can be sold",
+                total_bps
+            )
+        );
+
+        //only insert into the payout if the key isn't the token owner (we add their payout at the end)
+        for (account_id, percentage) in royalty.iter().filter(|(account_id, _)| **account_id != owner_id) {
+            payout.insert(account_id.clone(), royalty_to_payout(*percentage, balance));
+            total_perpetual += *percentage;
+        }
+    }
+
+    // payout to previous owner who gets 100% - total perpetual royalties
+    payout.insert(owner_id, royalty_to_payout(10_000 - total_perpetual, balance));
+
+    //route the rounding dust to the series owner
+    let allocated: Balance = payout.values().map(|amount| amount.0).sum();
+    if allocated < balance {
+        let series_owner_payout = payout.entry(series_owner_id).or_insert(U128(0));
+        series_owner_payout.0 += balance - allocated;
+    }
+
+    Payout { payout }
+}
+
+/// The most owners kept in a token's provenance. Older entries are dropped first.
+pub const MAX_PROVENANCE_ENTRIES: usize = 20;
+
+//format a yoctoNEAR amount as a NEAR amount without trailing zeros, e.g. "1.5"
+pub(crate) fn format_near(amount: Balance) -> String {
+    const YOCTO_PER_NEAR: Balance = 10u128.pow(24);
+    let whole = amount / YOCTO_PER_NEAR;
+    let fraction = amount % YOCTO_PER_NEAR;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:024}", fraction);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
 }
 
 //calculate how many bytes the account ID is taking up
@@ -13,6 +112,48 @@ pub(crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u64 {
     account_id.as_str().len() as u64 + 4 + size_of::<u64>() as u64
 }
 
+//storage overhead NEAR charges for every key-value record in the trie
+const STORAGE_BYTES_PER_RECORD: u64 = 40;
+
+//how many bytes minting the passed in token adds to the contract state. `new_owner` is set if the owner has no
+//tokens yet, `new_holder` if they hold none of the series and `first_holder` if nobody holds the series yet
+pub(crate) fn bytes_for_token_mint(
+    token_id: &TokenId,
+    token: &Token,
+    new_owner: bool,
+    new_holder: bool,
+    first_holder: bool,
+) -> u64 {
+    let id_bytes = token_id.try_to_vec().unwrap().len() as u64;
+    let token_bytes = token.try_to_vec().unwrap().len() as u64;
+    let owner_bytes = token.owner_id.try_to_vec().unwrap().len() as u64;
+    let index_bytes = size_of::<u64>() as u64;
+    // Collection prefixes are a borsh enum tag (plus a 32 byte hash for inner collections) and a one byte suffix
+    let map_prefix_bytes = 2;
+    let inner_prefix_bytes = 34;
+
+    // tokens_by_id writes the key's index, the key itself and the token struct
+    let tokens_by_id = 3 * STORAGE_BYTES_PER_RECORD
+        + 3 * (map_prefix_bytes + index_bytes)
+        + 2 * id_bytes
+        + token_bytes;
+    // the series' and the owner's token sets each write the element's index and the element itself
+    let token_set = 2 * STORAGE_BYTES_PER_RECORD + 2 * (inner_prefix_bytes + index_bytes + id_bytes);
+    // a first-time owner also needs a tokens_per_owner entry holding their set's prefixes and length
+    let owner_entry = if new_owner {
+        STORAGE_BYTES_PER_RECORD + 1 + owner_bytes + 2 * (4 + inner_prefix_bytes) + index_bytes
+    } else {
+        0
+    };
+    // the provenance log starts with the first owner and when they got the token
+    let provenance = STORAGE_BYTES_PER_RECORD + 1 + id_bytes + 4 + owner_bytes + size_of::<u64>() as u64;
+    // the owner's holdings count for the series and, for a first-time holder, the series' unique owner count
+    let holdings = if new_holder { STORAGE_BYTES_PER_RECORD + 1 + index_bytes + owner_bytes + index_bytes } else { 0 };
+    let unique_owners = if first_holder { STORAGE_BYTES_PER_RECORD + 1 + index_bytes + index_bytes } else { 0 };
+
+    tokens_by_id + 2 * token_set + owner_entry + provenance + holdings + unique_owners
+}
+
 //refund the storage taken up by passed in approved account IDs and send the funds to the passed in account ID.
 pub(crate) fn refund_approved_account_ids_iter<'a, I>(
     account_id: AccountId,
@@ -38,6 +179,25 @@ pub(crate) fn refund_approved_account_ids(
     refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
 }
 
+//merge per-token entries into a token's `extra` metadata. If the series' extra is a JSON object its keys are kept,
+//any other non-empty extra is kept as a string under the "extra" key.
+pub(crate) fn token_extra(extra: Option<String>, entries: Vec<(&str, near_sdk::serde_json::Value)>) -> String {
+    let mut object = match extra.as_deref().map(near_sdk::serde_json::from_str) {
+        Some(Ok(near_sdk::serde_json::Value::Object(object))) => object,
+        _ => {
+            let mut object = near_sdk::serde_json::Map::new();
+            if let Some(extra) = extra.filter(|extra| !extra.is_empty()) {
+                object.insert("extra".to_string(), json!(extra));
+            }
+            object
+        }
+    };
+    for (key, value) in entries {
+        object.insert(key.to_string(), value);
+    }
+    near_sdk::serde_json::Value::Object(object).to_string()
+}
+
 //used to generate a unique prefix in our storage collections (this is to avoid data collisions)
 pub(crate) fn hash_account_id(account_id: &String) -> CryptoHash {
     //get the default hash
@@ -64,39 +224,117 @@ pub(crate) fn assert_at_least_one_yocto() {
     )
 }
 
-// Send all the non storage funds to the series owner
-pub(crate) fn payout_series_owner(storage_used: u64, price_per_token: Balance, owner_id: AccountId) {
+//accounts that can never receive NEAR, so a payout listing them would always fail
+const UNPAYABLE_ACCOUNTS: [&str; 1] = ["system"];
+
+//Assert that a royalty map can be paid out: the shares add up to more than 0 and at most 100% (10000 basis points),
+//and no recipient is an account that can never receive NEAR. The shares also can't add up to more than `max_bps`,
+//the contract-wide cap.
+//recipients are `AccountId`s, so malformed account IDs are already rejected when the call's arguments are parsed.
+//whether a well-formed account actually exists can't be checked synchronously, so a transfer to a missing account
+//still fails at payout time and the marketplace gets its NEAR back.
+pub(crate) fn assert_valid_royalty(royalty: &Option<HashMap<AccountId, u32>>, max_bps: u32) {
+    if let Some(royalty) = royalty {
+        for account_id in royalty.keys() {
+            require!(
+                !UNPAYABLE_ACCOUNTS.contains(&account_id.as_str()),
+                format!("Royalty recipient {} can't receive NEAR", account_id)
+            );
+        }
+        let total: u64 = royalty.values().map(|share| u64::from(*share)).sum();
+        require!(total > 0, "Royalty shares must add up to more than 0");
+        require!(total <= 10_000, "Royalty shares can't add up to more than 10000");
+        require!(
+            total <= u64::from(max_bps),
+            format!("Royalty shares can't add up to more than the contract's cap of {}", max_bps)
+        );
+    }
+}
+
+//Assert that a series price is either free (none or 0) or at least `min_price`, so a paid series can't be priced at
+//a few yoctoNEAR that only leave dust to transfer and refund
+pub(crate) fn assert_valid_price(price: Option<Balance>, min_price: Balance) {
+    if let Some(price) = price.filter(|price| *price > 0) {
+        require!(
+            price >= min_price,
+            format!("A series price must be 0 or at least {} yoctoNEAR", min_price)
+        );
+    }
+}
+
+//Assert that an optional text field is at most `max_length` characters long
+pub(crate) fn assert_max_length(field: &str, value: &Option<String>, max_length: usize) {
+    if let Some(value) = value {
+        require!(
+            value.chars().count() <= max_length,
+            format!("{} can be at most {} characters long", field, max_length)
+        );
+    }
+}
+
+//Assert that a series' title and description fit the length limits. Strings are always valid UTF-8 once parsed.
+pub(crate) fn assert_valid_series_metadata(metadata: &TokenMetadata) {
+    assert_max_length("Title", &metadata.title, MAX_TITLE_LENGTH);
+    assert_max_length("Description", &metadata.description, MAX_DESCRIPTION_LENGTH);
+}
+
+// Send the price to the series owner and the mint fee to the treasury, then refund whatever is left after storage
+// to `refund_to`. A mint is the primary sale, so the series owner always gets the full price: the royalty map only
+// applies to resales through `nft_payout`. The mint fee is charged on top and never comes out of the price.
+pub(crate) fn payout_series_owner(
+    storage_used: u64,
+    price_per_token: Balance,
+    owner_id: AccountId,
+    mint_fee: Balance,
+    treasury_id: AccountId,
+    refund_to: AccountId,
+) {
     //get how much it would cost to store the information
     let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
     //get the attached deposit
     let attached_deposit = env::attached_deposit();
 
     //make sure that the attached deposit is greater than or equal to the required cost
-    assert!(
-        attached_deposit >= required_cost + price_per_token,
-        "Must attach {} yoctoNEAR to cover storage and price per token {}",
-        required_cost,
-        price_per_token
+    require!(
+        attached_deposit >= required_cost + price_per_token + mint_fee,
+        ContractError::Underfunded.message(&format!(
+            "Must attach {} yoctoNEAR to cover storage, price per token {} and mint fee {}",
+            required_cost, price_per_token, mint_fee
+        ))
     );
 
-    // If there's a price for the token, transfer everything but the storage to the series owner
+    // If there's a price for the token, transfer it to the series owner
     if price_per_token > 0 {
-        Promise::new(owner_id).transfer(attached_deposit - required_cost);
+        Promise::new(owner_id).transfer(price_per_token);
+    }
+    // The protocol's cut goes to the treasury
+    if mint_fee > 0 {
+        Promise::new(treasury_id).transfer(mint_fee);
+    }
+
+    //refund the overpayment
+    let refund = attached_deposit - required_cost - price_per_token - mint_fee;
+    if refund > 1 {
+        Promise::new(refund_to).transfer(refund);
     }
 }
 
 //refund the initial deposit based on the amount of storage that was used up
 pub(crate) fn refund_deposit(storage_used: u64) {
+    refund_deposit_to(storage_used, env::predecessor_account_id())
+}
+
+//refund the initial deposit based on the amount of storage that was used up to `refund_to`
+pub(crate) fn refund_deposit_to(storage_used: u64, refund_to: AccountId) {
     //get how much it would cost to store the information
     let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
     //get the attached deposit
     let attached_deposit = env::attached_deposit();
 
     //make sure that the attached deposit is greater than or equal to the required cost
-    assert!(
+    require!(
         required_cost <= attached_deposit,
-        "Must attach {} yoctoNEAR to cover storage",
-        required_cost,
+        ContractError::Underfunded.message(&format!("Must attach {} yoctoNEAR to cover storage", required_cost))
     );
 
     //get the refund amount from the attached deposit - required cost
@@ -104,24 +342,412 @@ pub(crate) fn refund_deposit(storage_used: u64) {
 
     //if the refund is greater than 1 yocto NEAR, we refund the predecessor that amount
     if refund > 1 {
-        Promise::new(env::predecessor_account_id()).transfer(refund);
+        Promise::new(refund_to).transfer(refund);
     }
 }
 
 impl Contract {
     /// Ensure that the caller is the owner of the contract
     pub(crate) fn assert_contract_owner(&mut self) {
-        assert!(
+        require!(
             self.owner_id == env::predecessor_account_id(),
-            "only contract owner"
+            ContractError::Unauthorized.message("only contract owner")
+        )
+    }
+
+    /// Ensure that the caller is the owner of the passed in series
+    pub(crate) fn assert_series_owner(&self, series: &Series) {
+        require!(
+            series.owner_id == env::predecessor_account_id(),
+            ContractError::Unauthorized.message("Only the series owner can update the series")
         )
     }
 
-    //add a token to the set of tokens an owner has
+    //get a series, failing with `ContractError::SeriesNotFound` if it doesn't exist
+    pub(crate) fn internal_get_series(&self, series_id: SeriesId) -> Series {
+        self.series_by_id
+            .get(&series_id)
+            .unwrap_or_else(|| ContractError::SeriesNotFound.panic("Not a series"))
+    }
+
+    //approved creators can create series, and so can the contract owner even after removing themselves from the list
+    pub(crate) fn internal_can_create_series(&self, account_id: &AccountId) -> bool {
+        *account_id == self.owner_id || self.approved_creators.contains(account_id)
+    }
+
+    //insert a new series owned by `owner_id`, panicking if the ID is taken. Returns the series ID.
+    //series are never deleted, so an ID can't be freed and reused by a different program
+    pub(crate) fn internal_insert_series(&mut self, owner_id: AccountId, spec: SeriesSpec) -> SeriesId {
+        let id = spec.id;
+        self.internal_add_series_to_owner(&owner_id, id);
+        let claim_only = spec.claim_only.unwrap_or(false);
+        require!(!claim_only || spec.price.is_none(), "Only a free series can be claim-only");
+        assert_valid_price(spec.price.map(|price| price.0), self.min_series_price);
+        require!(
+            self.series_by_id
+                .insert(
+                    &id,
+                    &Series {
+                        metadata: spec.metadata,
+                        royalty: spec.royalty,
+                        tokens: UnorderedSet::new(StorageKey::SeriesByIdInner {
+                            // We get a new unique prefix for the collection
+                            account_id_hash: hash_account_id(&format!("{}{}", id, owner_id)),
+                        }),
+                        owner_id,
+                        price: spec.price.map(|p| p.into()),
+                        description: spec.description,
+                        external_url: spec.external_url,
+                        visible: spec.visible.unwrap_or(true),
+                        editions_minted: 0,
+                        revenue: 0,
+                        transferable: spec.transferable.unwrap_or(false),
+                        unique_per_account: spec.unique_per_account.unwrap_or(false),
+                        locked: false,
+                        claim_only,
+                        on_mint_callback: spec.on_mint_callback,
+                        minting_paused: false,
+                        collection_media: spec.collection_media,
+                        claim_deadline: spec.claim_deadline,
+                        eligible_receivers: None,
+                        //priced series have always been open sales, so they keep starting that way
+                        public_mint_enabled: spec.public_mint_enabled.unwrap_or(spec.price.is_some()),
+                        storage_payer: spec.storage_payer.unwrap_or_default(),
+                    },
+                )
+                .is_none(),
+            "collection ID already exists"
+        );
+        id
+    }
+
+    //get a token, failing with `ContractError::TokenNotFound` if it doesn't exist
+    pub(crate) fn internal_get_token(&self, token_id: &TokenId) -> Token {
+        self.tokens_by_id
+            .get(token_id)
+            .unwrap_or_else(|| ContractError::TokenNotFound.panic("No token"))
+    }
+
+    //append the token's new owner to its provenance, dropping the oldest entries beyond the cap
+    pub(crate) fn internal_record_owner(&mut self, token_id: &TokenId, owner_id: &AccountId) {
+        let mut provenance = self.provenance_by_token.get(token_id).unwrap_or_default();
+        provenance.push(ProvenanceEntry {
+            owner_id: owner_id.clone(),
+            timestamp: env::block_timestamp_ms(),
+        });
+        if provenance.len() > MAX_PROVENANCE_ENTRIES {
+            provenance.drain(..provenance.len() - MAX_PROVENANCE_ENTRIES);
+        }
+        self.provenance_by_token.insert(token_id, &provenance);
+    }
+
+    //check whether an account owns at least one token of the given series. Reads the holding counts, which cover
+    //tokens held since before they were kept once `index_series_holdings` has run over them
+    pub(crate) fn internal_holds_series_token(&self, account_id: &AccountId, series_id: SeriesId) -> bool {
+        self.series_holdings.get(&(series_id, account_id.clone())).unwrap_or(0) > 0
+    }
+
+    //find an account holding more than one token of the series, with how many it holds
+    pub(crate) fn internal_series_holder_of_many(&self, series: &Series) -> Option<(AccountId, u64)> {
+        let mut held_by_owner: HashMap<AccountId, u64> = HashMap::new();
+        for token_id in series.tokens.iter() {
+            let owner_id = self.internal_get_token(&token_id).owner_id;
+            let held = held_by_owner.entry(owner_id.clone()).or_insert(0);
+            *held += 1;
+            if *held > 1 {
+                return Some((owner_id, *held));
+            }
+        }
+        None
+    }
+
+    //mint a new token in the passed in series to the receiver, charging the series price or storage
+    //to the caller. Returns the ID of the minted token.
+    pub(crate) fn internal_mint(
+        &mut self,
+        series_id: SeriesId,
+        receiver_id: AccountId,
+        metadata_overrides: Option<TokenMetadata>,
+        refund_to: Option<AccountId>,
+    ) -> TokenId {
+        self.internal_mint_many(series_id, vec![(receiver_id, metadata_overrides)], refund_to, false, MintOptions::default())
+            .pop()
+            .unwrap()
+    }
+
+    //the price the caller pays per token of the series, after checking they're allowed to mint it.
+    //the series owner would be paying themselves, so they only cover storage (the copy limit still applies).
+    //`claim` means the caller already spent a claim allowance, which stands in for being an approved minter.
+    //anyone can mint a series open to public minting.
+    pub(crate) fn internal_mint_price(&self, series: &Series, claim: bool) -> Balance {
+        let predecessor = env::predecessor_account_id();
+        if !self.internal_may_mint(series, &predecessor, claim) {
+            let reason = if series.price.is_some() { "Public minting isn't open for this series" } else { "Not approved minter" };
+            ContractError::Unauthorized.panic(reason);
+        }
+        // If the series has a price, the caller pays it unless they own the series
+        match series.price {
+            Some(price) if predecessor != series.owner_id => price,
+            _ => 0,
+        }
+    }
+
+    //the bytes the next `nft_mint` of the series to `receiver_id` adds, with its storage charged to `payer_id`
+    pub(crate) fn internal_storage_for_next_mint(
+        &self,
+        series_id: SeriesId,
+        series: &Series,
+        receiver_id: &AccountId,
+        payer_id: &AccountId,
+    ) -> u64 {
+        let edition = series.editions_minted + 1;
+        let token = Token {
+            series_id,
+            owner_id: receiver_id.clone(),
+            approved_account_ids: Default::default(),
+            next_approval_id: 0,
+            metadata_overrides: None,
+            paid_amount: 0,
+            minted_at: 0,
+            edition,
+            storage_charge: Some(StorageCharge {
+                account_id: payer_id.clone(),
+                from_deposit: series.storage_payer != StoragePayer::Caller,
+                bytes: 0,
+            }),
+        };
+        bytes_for_token_mint(
+            &token_id_for_edition(series_id, edition),
+            &token,
+            self.tokens_per_owner.get(receiver_id).is_none(),
+            self.series_holdings.get(&(series_id, receiver_id.clone())).is_none(),
+            self.unique_owners_by_series.get(&series_id).is_none(),
+        )
+    }
+
+    //whether `account_id` is allowed to mint the series, leaving aside supply, pauses and receivers.
+    //the owner of a priced series may always mint it, since they'd only be paying themselves.
+    pub(crate) fn internal_may_mint(&self, series: &Series, account_id: &AccountId, claim: bool) -> bool {
+        let may_mint = claim || series.public_mint_enabled || self.approved_minters.contains(account_id);
+        may_mint || (series.price.is_some() && &series.owner_id == account_id)
+    }
+
+    //mint one token of the series per receiver, then take the price and storage for all of them out of the deposit.
+    //the rest of the deposit goes back to `refund_to`, or the caller if it's not given.
+    //`claim` is set when the caller is claiming with an allowance rather than minting as an approved minter.
+    //`request_key` remembers the last minted token under a caller-scoped request ID, paid for with the mint's storage.
+    //`external_id` is attached to the last minted token the same way and must not be taken already.
+    //so is `credential`, which must be JSON of at most `MAX_CREDENTIAL_LENGTH` characters.
+    pub(crate) fn internal_mint_many(
+        &mut self,
+        series_id: SeriesId,
+        receivers: Vec<(AccountId, Option<TokenMetadata>)>,
+        refund_to: Option<AccountId>,
+        claim: bool,
+        options: MintOptions,
+    ) -> Vec<TokenId> {
+        let MintOptions { request_key, external_id, credential } = options;
+        self.assert_operation_enabled(Operation::Mint);
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        // Get the series and how many tokens currently exist (edition number = cur_len + 1)
+        let mut series = self.internal_get_series(series_id);
+        require!(!series.minting_paused, ContractError::Paused.message("Series minting paused"));
+        if let Some(external_id) = &external_id {
+            require!(
+                self.tokens_by_external_id.get(external_id).is_none(),
+                format!("External ID {} is already used by another token", external_id)
+            );
+        }
+        assert_max_length("Credential", &credential, MAX_CREDENTIAL_LENGTH);
+        if let Some(credential) = &credential {
+            require!(
+                near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(credential).is_ok(),
+                "Credential must be valid JSON"
+            );
+        }
+
+        // Check if the series has a price per token. If it does, ensure the caller has attached at least that amount.
+        let price_per_token = self.internal_mint_price(&series, claim);
+        let total_price = price_per_token * receivers.len() as Balance;
+        if price_per_token > 0 {
+            require!(
+                env::attached_deposit() >= total_price,
+                ContractError::Underfunded.message("Need to attach at least enough to cover price")
+            );
+        }
+
+        // Check every receiver before anything is written, so a batch failing on its last receiver can't leave the
+        // earlier tokens, counters and indexes half-updated. Only the storage payment below depends on what the mint
+        // actually wrote, so it's checked afterwards and a failure there reverts the whole receipt.
+        // Ensure we won't overflow the number of copies minted
+        if let Some(copies) = series.metadata.copies {
+            require!(
+                series.tokens.len() + receivers.len() as u64 <= copies,
+                ContractError::SoldOut.message("cannot mint anymore NFTs for the given series. Limit reached")
+            );
+        }
+        for (index, (receiver_id, _)) in receivers.iter().enumerate() {
+            // Restricted series can only be minted directly to their eligible receivers
+            if !claim {
+                require!(
+                    series.is_eligible_receiver(receiver_id),
+                    ContractError::Unauthorized.message(&format!("{} isn't eligible to receive this series", receiver_id))
+                );
+            }
+
+            // Credential-like series grant at most one token per account, counting earlier receivers of the batch
+            if series.unique_per_account {
+                require!(
+                    !self.internal_holds_series_token(receiver_id, series_id)
+                        && !receivers[..index].iter().any(|(earlier_id, _)| earlier_id == receiver_id),
+                    ContractError::AlreadyHolds.message("Account already holds this badge")
+                );
+            }
+
+            // Make sure that the token doesn't exist
+            let edition = series.editions_minted + index as u64 + 1;
+            require!(
+                self.tokens_by_id.get(&token_id_for_edition(series_id, edition)).is_none(),
+                "Token already exists"
+            );
+        }
+
+        let refund_to = refund_to.unwrap_or_else(env::predecessor_account_id);
+        let mut token_ids = Vec::with_capacity(receivers.len());
+        let mut mint_logs = Vec::with_capacity(receivers.len());
+        //the storage each token took, so it can be charged to (and later refunded to) whoever pays for it
+        let mut storage_by_token = Vec::with_capacity(receivers.len());
+        for (receiver_id, metadata_overrides) in receivers {
+            let token_storage_usage = env::storage_usage();
+
+            // The token ID is stored internally as `${series_id}:${edition}`
+            series.editions_minted += 1;
+            series.revenue += price_per_token;
+            self.tokens_minted += 1;
+            let token_id = token_id_for_edition(series_id, series.editions_minted);
+            series.tokens.insert(&token_id);
+
+            //the account that pays for the token's storage. The bytes are filled in once they're known below,
+            //which doesn't change the token's size. The deposit's leftovers go to `refund_to`, so its storage does too
+            let storage_charge = match series.storage_payer {
+                StoragePayer::Caller => StorageCharge { account_id: refund_to.clone(), from_deposit: false, bytes: 0 },
+                StoragePayer::SeriesOwner => {
+                    StorageCharge { account_id: series.owner_id.clone(), from_deposit: true, bytes: 0 }
+                }
+                StoragePayer::Recipient => StorageCharge { account_id: receiver_id.clone(), from_deposit: true, bytes: 0 },
+            };
+            if storage_charge.from_deposit {
+                self.assert_storage_payment_approved(series_id, &storage_charge.account_id);
+            }
+
+            //specify the token struct that contains the owner ID
+            let token = Token {
+                // Series ID that the token belongs to
+                series_id,
+                //set the owner ID equal to the receiver ID passed into the function
+                owner_id: receiver_id,
+                //metadata fields that replace the ones derived from the series
+                metadata_overrides,
+                //record what was paid so the token can be refunded later
+                paid_amount: price_per_token,
+                minted_at: env::block_timestamp_ms(),
+                edition: series.editions_minted,
+                storage_charge: Some(storage_charge),
+                //we set the approved account IDs to the default value (an empty map)
+                approved_account_ids: Default::default(),
+                //the next approval ID is set to 0
+                next_approval_id: 0,
+            };
+
+            //insert the token ID and token struct
+            self.tokens_by_id.insert(&token_id, &token);
+
+            //call the internal method for adding the token to the owner
+            self.internal_add_token_to_owner(&token.owner_id, &token_id, series_id);
+            self.internal_record_owner(&token_id, &token.owner_id);
+
+            //notify the series' callback contract. It runs in its own receipt, so if it fails the mint still stands
+            if let Some(on_mint_callback) = series.on_mint_callback.clone() {
+                ext_mint_callback::ext(on_mint_callback)
+                    .with_static_gas(GAS_FOR_ON_BADGE_MINTED)
+                    .on_badge_minted(series_id, token_id.clone(), token.owner_id.clone());
+            }
+
+            mint_logs.push(NftMintLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // Vector of token IDs that were minted.
+                token_ids: vec![token_id.to_string()],
+                // An optional memo to include.
+                memo: None,
+            });
+            storage_by_token.push(env::storage_usage() - token_storage_usage);
+            token_ids.push(token_id);
+        }
+        self.series_by_id.insert(&series_id, &series);
+        if let (Some(key), Some(token_id)) = (request_key, token_ids.last()) {
+            self.mints_by_request_id.insert(&key, token_id);
+        }
+        if let (Some(external_id), Some(token_id)) = (external_id, token_ids.last()) {
+            self.tokens_by_external_id.insert(&external_id, token_id);
+            self.external_id_by_token.insert(token_id, &external_id);
+        }
+        if let (Some(credential), Some(token_id)) = (credential, token_ids.last()) {
+            self.credentials_by_token.insert(token_id, &credential);
+        }
+
+        // Log the mint event as per the events standard.
+        EventLogVariant::NftMint(mint_logs).emit();
+
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //record each token's share of the storage and take it out of the payer's storage deposit, unless the attached
+        //deposit pays. What was written after the loop (request and external IDs, credential) belongs to the last token
+        let mut unassigned = required_storage_in_bytes;
+        let last = token_ids.len() - 1;
+        for (index, (token_id, token_storage)) in token_ids.iter().zip(storage_by_token).enumerate() {
+            let bytes = if index == last { unassigned } else { token_storage.min(unassigned) };
+            unassigned -= bytes;
+            let mut token = self.tokens_by_id.get(token_id).unwrap();
+            let charge = token.storage_charge.as_mut().unwrap();
+            charge.bytes = bytes;
+            if charge.from_deposit {
+                self.internal_charge_storage_deposit(&charge.account_id, bytes);
+            }
+            self.tokens_by_id.insert(token_id, &token);
+        }
+        let caller_storage_in_bytes = match series.storage_payer {
+            StoragePayer::Caller => required_storage_in_bytes,
+            StoragePayer::Recipient | StoragePayer::SeriesOwner => 0,
+        };
+
+        // If there's some price or fee for the token, we'll pay them out. Otherwise, refund the excess deposit for storage to the caller
+        let total_fee = self.mint_fee * token_ids.len() as Balance;
+        if price_per_token > 0 || total_fee > 0 {
+            payout_series_owner(
+                caller_storage_in_bytes,
+                total_price,
+                series.owner_id,
+                total_fee,
+                self.treasury_id.clone(),
+                refund_to,
+            );
+        } else {
+            refund_deposit_to(caller_storage_in_bytes, refund_to);
+        }
+
+        token_ids
+    }
+
+    //add a token of the given series to the set of tokens an owner has
     pub(crate) fn internal_add_token_to_owner(
         &mut self,
         account_id: &AccountId,
         token_id: &TokenId,
+        series_id: SeriesId,
     ) {
         //get the set of tokens for the given account
         let mut tokens_set = self.tokens_per_owner.get(account_id).unwrap_or_else(|| {
@@ -141,13 +767,15 @@ impl Contract {
 
         //we insert that set for the given account ID.
         self.tokens_per_owner.insert(account_id, &tokens_set);
+        self.internal_add_series_holding(series_id, account_id);
     }
 
-    //remove a token from an owner (internal method and can't be called directly via CLI).
+    //remove a token of the given series from an owner (internal method and can't be called directly via CLI).
     pub(crate) fn internal_remove_token_from_owner(
         &mut self,
         account_id: &AccountId,
         token_id: &TokenId,
+        series_id: SeriesId,
     ) {
         //we get the set of tokens that the owner has
         let mut tokens_set = self
@@ -166,6 +794,211 @@ impl Contract {
             //if the token set is not empty, we simply insert it back for the account ID.
             self.tokens_per_owner.insert(account_id, &tokens_set);
         }
+        self.internal_remove_series_holding(series_id, account_id);
+    }
+
+    //add a series to the set of series an account owns
+    pub(crate) fn internal_add_series_to_owner(&mut self, account_id: &AccountId, series_id: SeriesId) {
+        let mut series_set = self.series_per_owner.get(account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::SeriesPerOwnerInner {
+                account_id_hash: hash_account_id(&account_id.to_string()),
+            })
+        });
+        series_set.insert(&series_id);
+        self.series_per_owner.insert(account_id, &series_set);
+    }
+
+    //record that a series changed hands in the per-owner series index
+    pub(crate) fn internal_move_series(&mut self, series_id: SeriesId, old_owner_id: &AccountId, new_owner_id: &AccountId) {
+        if let Some(mut series_set) = self.series_per_owner.get(old_owner_id) {
+            series_set.remove(&series_id);
+            if series_set.is_empty() {
+                self.series_per_owner.remove(old_owner_id);
+            } else {
+                self.series_per_owner.insert(old_owner_id, &series_set);
+            }
+        }
+        self.internal_add_series_to_owner(new_owner_id, series_id);
+    }
+
+    //count one more token of the series for the account, which becomes a new unique owner if it's their first
+    pub(crate) fn internal_add_series_holding(&mut self, series_id: SeriesId, account_id: &AccountId) {
+        let key = (series_id, account_id.clone());
+        let held = self.series_holdings.get(&key).unwrap_or(0);
+        self.series_holdings.insert(&key, &(held + 1));
+        if held == 0 {
+            let unique_owners = self.unique_owners_by_series.get(&series_id).unwrap_or(0);
+            self.unique_owners_by_series.insert(&series_id, &(unique_owners + 1));
+        }
+    }
+
+    //set how many tokens of the series the account holds, adding or removing it as a unique owner if it starts or
+    //stops holding any. Returns whether the count changed.
+    pub(crate) fn internal_set_series_holding(&mut self, series_id: SeriesId, account_id: &AccountId, held: u64) -> bool {
+        let key = (series_id, account_id.clone());
+        let previous = self.series_holdings.get(&key).unwrap_or(0);
+        if previous == held {
+            return false;
+        }
+        if held == 0 {
+            self.series_holdings.remove(&key);
+        } else {
+            self.series_holdings.insert(&key, &held);
+        }
+
+        let unique_owners = self.unique_owners_by_series.get(&series_id).unwrap_or(0);
+        if previous == 0 {
+            self.unique_owners_by_series.insert(&series_id, &(unique_owners + 1));
+        } else if held == 0 {
+            if unique_owners > 1 {
+                self.unique_owners_by_series.insert(&series_id, &(unique_owners - 1));
+            } else {
+                self.unique_owners_by_series.remove(&series_id);
+            }
+        }
+        true
+    }
+
+    //recount the account's holdings from the tokens in its per-owner set, for every series it holds plus
+    //`other_series` (series it may have stopped holding). Returns how many counts changed.
+    pub(crate) fn internal_recount_series_holdings(&mut self, account_id: &AccountId, other_series: HashSet<SeriesId>) -> u64 {
+        let mut held_by_series: HashMap<SeriesId, u64> = other_series.into_iter().map(|series_id| (series_id, 0)).collect();
+        if let Some(tokens) = self.tokens_per_owner.get(account_id) {
+            for token_id in tokens.iter() {
+                if let Some(token) = self.tokens_by_id.get(&token_id) {
+                    *held_by_series.entry(token.series_id).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut changed = 0;
+        for (series_id, held) in held_by_series {
+            if self.internal_set_series_holding(series_id, account_id, held) {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    //count one less token of the series for the account, which stops being a unique owner once they hold none
+    pub(crate) fn internal_remove_series_holding(&mut self, series_id: SeriesId, account_id: &AccountId) {
+        let key = (series_id, account_id.clone());
+        let held = self.series_holdings.get(&key).unwrap_or(0);
+        //tokens held from before the counts were introduced were never counted
+        if held == 0 {
+            return;
+        }
+        if held > 1 {
+            self.series_holdings.insert(&key, &(held - 1));
+            return;
+        }
+        self.series_holdings.remove(&key);
+        let unique_owners = self.unique_owners_by_series.get(&series_id).unwrap_or(0);
+        if unique_owners > 1 {
+            self.unique_owners_by_series.insert(&series_id, &(unique_owners - 1));
+        } else {
+            self.unique_owners_by_series.remove(&series_id);
+        }
+    }
+
+    //burns the token, removing it from its owner and its series (internal method and can't be called directly via CLI).
+    //returns the burned token so callers can act on its previous state.
+    pub(crate) fn internal_burn(
+        &mut self,
+        token_id: &TokenId,
+        authorized_id: Option<String>,
+        reason: Option<RevokeReason>,
+        memo: Option<String>,
+    ) -> Token {
+        let mut refunds = StorageRefunds::default();
+        let token = self.internal_remove_token(token_id, &mut refunds);
+        self.internal_pay_storage_refunds(refunds);
+
+        // Log the burn event as per the events standard.
+        EventLogVariant::NftBurn(vec![NftBurnLog {
+            // Owner of the token.
+            owner_id: token.owner_id.to_string(),
+            // The account that burned the token on behalf of the owner, if any.
+            authorized_id,
+            // Vector of token IDs that were burned.
+            token_ids: vec![token_id.to_string()],
+            // Why the token was revoked, if given.
+            reason,
+            // An optional memo to include.
+            memo,
+        }]).emit();
+
+        token
+    }
+
+    //deletes the token from its owner, its series and the token map without logging anything, refunding the
+    //storage its approvals took to the owner. Returns the removed token.
+    pub(crate) fn internal_remove_token(&mut self, token_id: &TokenId, refunds: &mut StorageRefunds) -> Token {
+        self.assert_operation_enabled(Operation::Burn);
+        //the swap offer's storage goes back to whoever made it, so it's released before measuring the token's
+        self.internal_remove_swap_offer(token_id);
+        let initial_storage_usage = env::storage_usage();
+        //remove the token and make sure it existed
+        let token = self
+            .tokens_by_id
+            .remove(token_id)
+            .unwrap_or_else(|| ContractError::TokenNotFound.panic("No token"));
+
+        //remove the token from its owner and from its series
+        self.internal_remove_token_from_owner(&token.owner_id, token_id, token.series_id);
+        self.provenance_by_token.remove(token_id);
+        self.tokens_burned += 1;
+        self.recent_burns.record(token_id);
+        self.credentials_by_token.remove(token_id);
+        if let Some(external_id) = self.external_id_by_token.remove(token_id) {
+            self.tokens_by_external_id.remove(&external_id);
+        }
+        if let Some(mut series) = self.series_by_id.get(&token.series_id) {
+            series.tokens.remove(token_id);
+            self.series_by_id.insert(&token.series_id, &series);
+        }
+
+        //we refund the owner for releasing the storage used up by the approved account IDs
+        if !token.approved_account_ids.is_empty() {
+            refund_approved_account_ids(token.owner_id.clone(), &token.approved_account_ids);
+        }
+
+        //whoever paid for the token's storage gets it back, as far as the burn actually released it (the burn log
+        //keeps a little). The approvals were refunded above, so they're left out
+        if let Some(charge) = &token.storage_charge {
+            let approvals: u64 = token.approved_account_ids.keys().map(bytes_for_approved_account_id).sum();
+            let released = initial_storage_usage.saturating_sub(env::storage_usage()).saturating_sub(approvals);
+            refunds.add(charge, charge.bytes.min(released));
+        }
+
+        token
+    }
+
+    //move a token to `receiver_id` without any authorization checks, resetting its approvals
+    pub(crate) fn internal_reassign_token(&mut self, token: &Token, token_id: &TokenId, receiver_id: &AccountId) {
+        //a swap offer was made by the old owner, so it goes with them
+        self.internal_remove_swap_offer(token_id);
+        //we remove the token from it's current owner's set
+        self.internal_remove_token_from_owner(&token.owner_id, token_id, token.series_id);
+        //we then add the token to the receiver_id's set
+        self.internal_add_token_to_owner(receiver_id, token_id, token.series_id);
+
+        //we create a new token struct
+        let new_token = Token {
+            series_id: token.series_id,
+            owner_id: receiver_id.clone(),
+            //reset the approval account IDs
+            approved_account_ids: Default::default(),
+            next_approval_id: token.next_approval_id,
+            metadata_overrides: token.metadata_overrides.clone(),
+            paid_amount: token.paid_amount,
+            minted_at: token.minted_at,
+            edition: token.edition,
+            storage_charge: token.storage_charge.clone(),
+        };
+        //insert that new token into the tokens_by_id, replacing the old entry
+        self.tokens_by_id.insert(token_id, &new_token);
+        self.internal_record_owner(token_id, receiver_id);
     }
 
     //transfers the NFT to the receiver_id (internal method and can't be called directly via CLI).
@@ -178,15 +1011,27 @@ impl Contract {
         approval_id: Option<u64>,
         memo: Option<String>,
     ) -> Token {
+        self.assert_operation_enabled(Operation::Transfer);
+
         //get the token object by passing in the token_id
-        let token = self.tokens_by_id.get(token_id).expect("No token");
+        let token = self.internal_get_token(token_id);
+
+        //badges only go to allowlisted accounts. Every transfer path (transfers, transfer calls, payouts and swaps)
+        //comes through here
+        require!(
+            self.allowed_transfers.contains(receiver_id),
+            ContractError::Soulbound.message("Transfer not allowed to this address")
+        );
 
+        //the owner can always transfer their own token and any approval_id they pass is ignored.
         //if the sender doesn't equal the owner, we check if the sender is in the approval list
-        if sender_id != &token.owner_id {
+        let sender_is_owner = sender_id == &token.owner_id;
+        if !sender_is_owner {
             //if the token's approved account IDs doesn't contain the sender, we panic
-            if !token.approved_account_ids.contains_key(sender_id) {
-                env::panic_str("Unauthorized");
-            }
+            require!(
+                token.approved_account_ids.contains_key(sender_id),
+                ContractError::Unauthorized.message("Unauthorized")
+            );
 
             // If they included an approval_id, check if the sender's actual approval_id is the same as the one included
             if let Some(enforced_approval_id) = approval_id {
@@ -212,21 +1057,7 @@ impl Contract {
             "The token owner and the receiver should be different"
         );
 
-        //we remove the token from it's current owner's set
-        self.internal_remove_token_from_owner(&token.owner_id, token_id);
-        //we then add the token to the receiver_id's set
-        self.internal_add_token_to_owner(receiver_id, token_id);
-
-        //we create a new token struct
-        let new_token = Token {
-            series_id: token.series_id,
-            owner_id: receiver_id.clone(),
-            //reset the approval account IDs
-            approved_account_ids: Default::default(),
-            next_approval_id: token.next_approval_id,
-        };
-        //insert that new token into the tokens_by_id, replacing the old entry
-        self.tokens_by_id.insert(token_id, &new_token);
+        self.internal_reassign_token(&token, token_id, receiver_id);
 
         //if there was some memo attached, we log it.
         if let Some(memo) = memo.as_ref() {
@@ -235,34 +1066,24 @@ impl Contract {
 
         // Default the authorized ID to be None for the logs.
         let mut authorized_id = None;
-        //if the approval ID was provided, set the authorized ID equal to the sender
-        if approval_id.is_some() {
+        //if an approved account (rather than the owner) sent the token, set the authorized ID equal to the sender
+        if !sender_is_owner {
             authorized_id = Some(sender_id.to_string());
         }
 
-        // Construct the transfer log as per the events standard.
-        let nft_transfer_log: EventLog = EventLog {
-            // Standard name ("nep171").
-            standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
-            version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
-            event: EventLogVariant::NftTransfer(vec![NftTransferLog {
-                // The optional authorized account ID to transfer the token on behalf of the old owner.
-                authorized_id,
-                // The old owner's account ID.
-                old_owner_id: token.owner_id.to_string(),
-                // The account ID of the new owner of the token.
-                new_owner_id: receiver_id.to_string(),
-                // A vector containing the token IDs as strings.
-                token_ids: vec![token_id.to_string()],
-                // An optional memo to include.
-                memo,
-            }]),
-        };
-
-        // Log the serialized json.
-        env::log_str(&nft_transfer_log.to_string());
+        // Log the transfer event as per the events standard.
+        EventLogVariant::NftTransfer(vec![NftTransferLog {
+            // The optional authorized account ID to transfer the token on behalf of the old owner.
+            authorized_id,
+            // The old owner's account ID.
+            old_owner_id: token.owner_id.to_string(),
+            // The account ID of the new owner of the token.
+            new_owner_id: receiver_id.to_string(),
+            // A vector containing the token IDs as strings.
+            token_ids: vec![token_id.to_string()],
+            // An optional memo to include.
+            memo,
+        }]).emit();
 
         //return the previous token object that was transferred.
         token
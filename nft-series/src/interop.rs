@@ -0,0 +1,217 @@
+use crate::*;
+use near_sdk::json_types::U64;
+use near_sdk::{ext_contract, Gas};
+
+/// The interface other contracts use to gate features on badge ownership.
+/// `has_badge` is a view, so it can also be queried off-chain. From a contract, call it and read the result in a callback:
+///
+/// ```ignore
+/// ext_badges::ext(badges_account_id)
+///     .with_static_gas(Gas(5_000_000_000_000))
+///     .has_badge(account_id, series_id)
+///     .then(Self::ext(env::current_account_id()).on_has_badge())
+/// ```
+///
+/// Contracts awarding badges (e.g. a quest system) call `nft_mint` the same way. The calling contract must be an
+/// approved minter and pays the storage; `receiver_id` is the person receiving the badge:
+///
+/// ```ignore
+/// ext_badges::ext(badges_account_id)
+///     .with_attached_deposit(STORAGE_DEPOSIT)
+///     .nft_mint(U64(series_id), player_id, None, Some(quest_id), None, None)
+/// ```
+#[ext_contract(ext_badges)]
+pub trait Badges {
+    fn has_badge(&self, account_id: AccountId, series_id: SeriesId) -> bool;
+
+    fn nft_mint(
+        &mut self,
+        id: U64,
+        receiver_id: AccountId,
+        refund_to: Option<AccountId>,
+        request_id: Option<String>,
+        external_id: Option<String>,
+        credential: Option<String>,
+    ) -> TokenId;
+}
+
+/// Gas attached to the `on_badge_minted` notification sent after each mint of a series with a mint callback
+pub const GAS_FOR_ON_BADGE_MINTED: Gas = Gas(10_000_000_000_000);
+
+/// The method a series' mint callback contract implements. It's called after every mint of the series
+/// with the new token's details. Its result is ignored and a failure doesn't revert the mint.
+#[ext_contract(ext_mint_callback)]
+pub trait MintCallback {
+    fn on_badge_minted(&mut self, series_id: SeriesId, token_id: TokenId, owner_id: AccountId);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Check whether an account holds at least one token of the series
+    pub fn has_badge(&self, account_id: AccountId, series_id: SeriesId) -> bool {
+        self.internal_holds_series_token(&account_id, series_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts};
+    use near_sdk::{testing_env, PromiseResult};
+
+    /// A consumer contract that unlocks a feature for badge holders
+    struct Consumer {
+        badges_account_id: AccountId,
+    }
+
+    impl Consumer {
+        fn unlock(&self, account_id: AccountId, series_id: SeriesId) -> Promise {
+            ext_badges::ext(self.badges_account_id.clone())
+                .with_static_gas(Gas(5_000_000_000_000))
+                .has_badge(account_id, series_id)
+        }
+
+        fn on_has_badge(&self) -> bool {
+            match env::promise_result(0) {
+                PromiseResult::Successful(value) => near_sdk::serde_json::from_slice(&value).unwrap(),
+                _ => false,
+            }
+        }
+    }
+
+    /// A quest contract that awards a badge to players who complete a quest
+    struct Quests {
+        badges_account_id: AccountId,
+        series_id: SeriesId,
+    }
+
+    impl Quests {
+        fn complete(&self, player_id: AccountId, quest_id: String) -> Promise {
+            ext_badges::ext(self.badges_account_id.clone())
+                .with_attached_deposit(STORAGE_DEPOSIT)
+                .nft_mint(U64(self.series_id), player_id, None, Some(quest_id), None, None)
+        }
+    }
+
+    fn quests_account() -> AccountId {
+        "quests.near".parse().unwrap()
+    }
+
+    //capture the mint the quest contract sends and run it on the badges contract, called by the quest contract
+    //on behalf of a transaction the player signed
+    fn run_quest_mint(contract: &mut Contract, quests: &Quests, player_id: AccountId) -> TokenId {
+        let mut context = set_context(player_id.clone(), 0);
+        quests.complete(player_id.clone(), "quest-1".to_string());
+        let receipt = get_created_receipts().pop().unwrap();
+        let (args, deposit) = match &receipt.actions[0] {
+            VmAction::FunctionCall { function_name, args, deposit, .. } if function_name == "nft_mint" => {
+                (args.clone(), *deposit)
+            }
+            action => panic!("unexpected action {:?}", action),
+        };
+        assert_eq!(receipt.receiver_id, quests.badges_account_id);
+
+        testing_env!(context
+            .predecessor_account_id(quests_account())
+            .signer_account_id(player_id)
+            .attached_deposit(deposit)
+            .build());
+        let args: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(&args).unwrap();
+        contract.nft_mint(
+            U64(args["id"].as_str().unwrap().parse().unwrap()),
+            args["receiver_id"].as_str().unwrap().parse().unwrap(),
+            None,
+            args["request_id"].as_str().map(str::to_string),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn approved_minter_contract_mints_a_badge_to_the_player() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.add_approved_minter(quests_account());
+        let quests = Quests { badges_account_id: "badges.near".parse().unwrap(), series_id: 1 };
+
+        let token_id = run_quest_mint(&mut contract, &quests, accounts(1));
+
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+        //the quest contract paid for the mint, so it gets the unused deposit back, not the player
+        assert_eq!(transfers_to(&quests_account()).len(), 1);
+        assert!(transfers_to(&accounts(1)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: Not approved minter")]
+    fn unapproved_minter_contract_cant_mint_even_if_the_signer_is_a_minter() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let quests = Quests { badges_account_id: "badges.near".parse().unwrap(), series_id: 1 };
+
+        //the contract owner is an approved minter, but only the calling contract is checked
+        run_quest_mint(&mut contract, &quests, owner());
+    }
+
+    #[test]
+    fn has_badge_reflects_ownership() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+
+        assert!(contract.has_badge(accounts(1), 1));
+        assert!(!contract.has_badge(accounts(2), 1));
+        assert!(!contract.has_badge(accounts(1), 2));
+    }
+
+    #[test]
+    fn consumer_queries_badge_ownership_in_a_callback() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+        let consumer = Consumer { badges_account_id: "badges.near".parse().unwrap() };
+
+        set_context(accounts(1), 0);
+        consumer.unlock(accounts(1), 1);
+        let receipt = get_created_receipts().pop().unwrap();
+        let args = match &receipt.actions[0] {
+            VmAction::FunctionCall { function_name, args, .. } if function_name == "has_badge" => args.clone(),
+            action => panic!("unexpected action {:?}", action),
+        };
+
+        //answer the query the way the badges contract would and hand the result to the consumer's callback
+        let query: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(&args).unwrap();
+        let answer = contract.has_badge(query["account_id"].as_str().unwrap().parse().unwrap(), query["series_id"].as_u64().unwrap());
+        set_callback_context(PromiseResult::Successful(near_sdk::serde_json::to_vec(&answer).unwrap()));
+
+        assert_eq!(receipt.receiver_id, consumer.badges_account_id);
+        assert!(consumer.on_has_badge());
+    }
+
+    #[test]
+    fn mint_notifies_the_series_callback_contract() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_mint_callback(1, Some("quests.near".parse().unwrap()));
+
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        //the mint also refunds unused deposit, so pick the receipt sent to the callback contract
+        let receipt = get_created_receipts()
+            .into_iter()
+            .find(|receipt| receipt.receiver_id.as_str() == "quests.near")
+            .unwrap();
+        match &receipt.actions[0] {
+            VmAction::FunctionCall { function_name, args, .. } => {
+                let args: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(args).unwrap();
+                assert_eq!(function_name, "on_badge_minted");
+                assert_eq!(args["series_id"], 1);
+                assert_eq!(args["token_id"], token_id);
+                assert_eq!(args["owner_id"], accounts(1).to_string());
+            }
+            action => panic!("unexpected action {:?}", action),
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
@@ -10,28 +10,59 @@ use std::collections::HashMap;
 use near_sdk::serde_json::json;
 
 pub use crate::approval::*;
+pub use crate::burn::BurnLog;
+pub use crate::claims::ClaimAllowance;
+pub use crate::errors::ContractError;
 pub use crate::events::*;
+pub use crate::interop::{ext_badges, ext_mint_callback, Badges, MintCallback};
 use crate::internal::*;
 pub use crate::metadata::*;
 pub use crate::nft_core::*;
-pub use crate::owner::*;
+pub use crate::operations::{Operation, OperationFlags};
 pub use crate::royalty::*;
-pub use crate::series::*;
+pub use crate::series::SeriesSpec;
+pub use crate::storage::{StorageCharge, StoragePayer};
+pub use crate::swap::SwapOffer;
 
 mod approval;
+mod burn;
+mod claims;
 mod enumeration;
+mod errors;
 mod events;
 mod internal;
+mod interop;
 mod metadata;
+mod migrate;
 mod nft_core;
+mod operations;
 mod owner;
 mod royalty;
 mod series;
+// kept for `OpenCollection` users, nothing in this contract reads it
+#[allow(dead_code)]
+mod series_open;
+mod storage;
+mod swap;
+#[cfg(test)]
+mod test_utils;
 
-/// This spec can be treated like a version of the standard.
-pub const NFT_METADATA_SPEC: &str = "1.0.0";
+/// The NEP-171 version stamped on every event this contract emits (1.1.0 added the metadata update events).
+pub const NFT_EVENTS_VERSION: &str = "1.1.0";
 /// This is the name of the NFT standard we're using
 pub const NFT_STANDARD_NAME: &str = "nep171";
+/// The standard name of the events that aren't part of NEP-171, e.g. admin transfers and treasury updates
+pub const BADGES_STANDARD_NAME: &str = "devhub_badges";
+/// The version stamped on the `devhub_badges` events
+pub const BADGES_EVENTS_VERSION: &str = "1.0.0";
+/// The most token IDs listed in a series-wide `nft_metadata_update` event. Indexers refresh the rest by series ID.
+pub const MAX_METADATA_UPDATE_TOKEN_IDS: u64 = 100;
+/// The longest series title, in characters
+pub const MAX_TITLE_LENGTH: usize = 256;
+/// The longest series or badge program description, in characters
+pub const MAX_DESCRIPTION_LENGTH: usize = 2000;
+/// The longest credential JSON attached to a token, in characters
+pub const MAX_CREDENTIAL_LENGTH: usize = 2000;
 
 
 // Represents the series type. All tokens will derive this data.
@@ -39,15 +70,48 @@ pub const NFT_STANDARD_NAME: &str = "nep171";
 pub struct Series {
     // Metadata including title, num copies etc.. that all tokens will derive from
     metadata: TokenMetadata,
-    // Royalty used for all tokens in the collection
+    // Royalty used for all tokens in the collection. It only splits secondary sales (`nft_payout`), never the mint price
     royalty: Option<HashMap<AccountId, u32>>,
     // Set of tokens in the collection
     tokens: UnorderedSet<TokenId>,
     // What is the price of each token in this series? If this is specified, when minting,
     // Users will need to attach enough $NEAR to cover the price.
+    // This is the primary sale: the whole price goes to the series owner, whatever the royalty says.
     price: Option<Balance>,
     // Owner of the collection
     owner_id: AccountId,
+    // Description of the badge program itself (as opposed to the per-token metadata description)
+    description: Option<String>,
+    // Link to more information about the badge program
+    external_url: Option<String>,
+    // Whether the series shows up in public listings. Hidden series can be staged before launch.
+    visible: bool,
+    // How many editions have ever been minted. Token IDs use this so burned editions are never reused.
+    editions_minted: u64,
+    // Net amount paid for the series' tokens (mint payments minus refunds)
+    revenue: Balance,
+    // Whether holders can trade the series' tokens with each other (e.g. collectibles). Badges are soulbound by default.
+    transferable: bool,
+    // Whether an account can hold at most one token of the series (e.g. credentials)
+    unique_per_account: bool,
+    // Whether the series' pricing is frozen for good
+    locked: bool,
+    // Whether the series is only handed out for free by approved minters (it can never have a price)
+    claim_only: bool,
+    // Contract notified through `on_badge_minted` after each mint of the series
+    on_mint_callback: Option<AccountId>,
+    // Whether minting new tokens of the series is temporarily stopped
+    minting_paused: bool,
+    // Banner/collection image for the program in listings, separate from the per-token `media`
+    collection_media: Option<String>,
+    // When claims with `nft_claim` stop being accepted, Unix epoch in milliseconds. Regular mints aren't affected.
+    claim_deadline: Option<u64>,
+    // If set, approved minters can only mint the series to these accounts (e.g. verified contributors). Claims aren't affected.
+    eligible_receivers: Option<UnorderedSet<AccountId>>,
+    // Whether anyone can mint the series with `nft_mint`, not just approved minters (e.g. the public phase of a drop)
+    public_mint_enabled: bool,
+    // Who pays for the storage of the series' mints
+    storage_payer: StoragePayer,
 }
 
 impl Series {
@@ -66,10 +130,80 @@ impl Series {
     pub fn update_owner_id(&mut self, owner_id: AccountId) {
         self.owner_id = owner_id;
     }
+
+    pub fn update_description(&mut self, description: Option<String>) {
+        self.description = description;
+    }
+
+    pub fn update_external_url(&mut self, external_url: Option<String>) {
+        self.external_url = external_url;
+    }
+
+    pub fn update_collection_media(&mut self, collection_media: Option<String>) {
+        self.collection_media = collection_media;
+    }
+
+    pub fn update_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn update_transferable(&mut self, transferable: bool) {
+        self.transferable = transferable;
+    }
+
+    pub fn update_unique_per_account(&mut self, unique_per_account: bool) {
+        self.unique_per_account = unique_per_account;
+    }
+
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    pub fn update_claim_only(&mut self, claim_only: bool) {
+        self.claim_only = claim_only;
+    }
+
+    pub fn update_on_mint_callback(&mut self, on_mint_callback: Option<AccountId>) {
+        self.on_mint_callback = on_mint_callback;
+    }
+
+    pub fn update_minting_paused(&mut self, minting_paused: bool) {
+        self.minting_paused = minting_paused;
+    }
+
+    pub fn update_claim_deadline(&mut self, claim_deadline: Option<u64>) {
+        self.claim_deadline = claim_deadline;
+    }
+
+    pub fn update_public_mint_enabled(&mut self, public_mint_enabled: bool) {
+        self.public_mint_enabled = public_mint_enabled;
+    }
+
+    pub fn update_storage_payer(&mut self, storage_payer: StoragePayer) {
+        self.storage_payer = storage_payer;
+    }
+
+    /// Whether `account_id` may receive a directly minted token of the series
+    pub fn is_eligible_receiver(&self, account_id: &AccountId) -> bool {
+        self.eligible_receivers.as_ref().is_none_or(|eligible| eligible.contains(account_id))
+    }
+
+    /// Whether the series' claim deadline has passed
+    pub fn claim_window_closed(&self) -> bool {
+        self.claim_deadline.is_some_and(|deadline| env::block_timestamp_ms() > deadline)
+    }
 }
 
 pub type SeriesId = u64;
 
+/// Returned by batch allowlist insertions to report how many entries were actually new.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchInsertResult {
+    pub added: u64,
+    pub already_present: u64,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
@@ -82,6 +216,10 @@ pub struct Contract {
     //approved users that can create series
     pub approved_creators: LookupSet<AccountId>,
 
+    //enumerable copies of the approved minters and creators, for the list views
+    pub approved_minters_list: UnorderedSet<AccountId>,
+    pub approved_creators_list: UnorderedSet<AccountId>,
+
     //Map the collection ID (stored in Token obj) to the collection data
     pub series_by_id: UnorderedMap<SeriesId, Series>,
 
@@ -96,7 +234,69 @@ pub struct Contract {
      
     // Add a new field for the allowed addresses
     pub allowed_transfers: UnorderedSet<AccountId>,
-    
+
+    //maps a creator-supplied external ID to the series it created, so retried creations are no-ops
+    pub series_by_external_id: LookupMap<String, SeriesId>,
+
+    //keeps the most recent owners of each token, oldest first
+    pub provenance_by_token: LookupMap<TokenId, Vec<ProvenanceEntry>>,
+
+    //once set, the contract metadata can never be updated again
+    pub metadata_locked: bool,
+
+    //how many tokens of a series an account holds, keyed by (series ID, account)
+    pub series_holdings: LookupMap<(SeriesId, AccountId), u64>,
+
+    //how many distinct accounts hold at least one token of each series
+    pub unique_owners_by_series: LookupMap<SeriesId, u64>,
+
+    //how many tokens of a series an account may claim for itself, keyed by (series ID, account)
+    pub claim_allowances: LookupMap<(SeriesId, AccountId), ClaimAllowance>,
+
+    //maps a minter-supplied request ID to the token it minted, so retried mints are no-ops
+    pub mints_by_request_id: LookupMap<String, TokenId>,
+
+    //maps an issuer-supplied external ID (e.g. a database row ID) to its token, and back
+    pub tokens_by_external_id: LookupMap<String, TokenId>,
+    pub external_id_by_token: LookupMap<TokenId, String>,
+
+    //where mint fees and other protocol charges are sent. Defaults to the contract owner
+    pub treasury_id: AccountId,
+
+    //flat fee in yoctoNEAR charged on top of the price for every minted token and sent to the treasury
+    pub mint_fee: Balance,
+
+    //how many tokens were ever minted and burned
+    pub tokens_minted: u64,
+    pub tokens_burned: u64,
+
+    //structured claims (issuer, skill, score...) attached to credential badges at mint, as a JSON string
+    pub credentials_by_token: LookupMap<TokenId, String>,
+
+    //the most royalty (in basis points) any series can set. 10000 means no cap
+    pub max_royalty_bps: u32,
+
+    //operations the contract owner switched off one at a time, e.g. minting during an incident
+    pub disabled_operations: OperationFlags,
+
+    //the IDs of the latest burned tokens, for indexers to purge
+    pub recent_burns: BurnLog,
+
+    //NEAR accounts set aside to pay for mint storage of series whose storage payer isn't the caller
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    //the sum of every storage deposit, which belongs to the depositors and is never surplus
+    pub storage_deposits_total: Balance,
+    //accounts that agreed to pay for other callers' mints of a series out of their storage deposit
+    pub storage_payment_approvals: LookupSet<(SeriesId, AccountId)>,
+
+    //keeps track of all the series IDs owned by a given account
+    pub series_per_owner: LookupMap<AccountId, UnorderedSet<SeriesId>>,
+
+    //standing offers to swap a token for another specific token, keyed by the offered token
+    pub swap_offers: LookupMap<TokenId, SwapOffer>,
+
+    //the lowest non-zero price in yoctoNEAR a series can set, so paid series can't be priced at dust. 0 means no minimum
+    pub min_series_price: Balance,
 }
 
 
@@ -113,6 +313,26 @@ pub enum StorageKey {
     TokensById,
     NFTContractMetadata,
     AllowedTransfers,
+    SeriesByExternalId,
+    OpenCollectionTokens,
+    OpenCollectionSeries,
+    ProvenanceByToken,
+    SeriesHoldings,
+    UniqueOwnersBySeries,
+    ClaimAllowances,
+    MintsByRequestId,
+    TokensByExternalId,
+    ExternalIdByToken,
+    CredentialsByToken,
+    EligibleReceivers { series_id: SeriesId },
+    RecentBurns,
+    StorageDeposits,
+    SeriesPerOwner,
+    SeriesPerOwnerInner { account_id_hash: CryptoHash },
+    ApprovedMintersList,
+    ApprovedCreatorsList,
+    SwapOffers,
+    StoragePaymentApprovals,
 }
 
 
@@ -139,19 +359,23 @@ impl Contract {
             reference_hash: None,
         };
     
-        let event_data = json!({
-            "standard": "nep171",
-            "version": "1.1.0",
-            "event": "contract_metadata_update",
-            "data": metadata
-        });
-        env::log_str(&event_data.to_string());
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog {
+            metadata: Some(metadata.clone()),
+            ..Default::default()
+        }])
+        .emit();
     
         Self::new(owner_id, metadata)
     }
    
+    // `owner_id` stays in the JSON arguments for existing callers; the caller is checked instead
+    #[allow(unused_variables)]
     pub fn update_metadata(&mut self, owner_id: AccountId, metadata: NFTContractMetadata) {
-        require!(env::predecessor_account_id() == self.owner_id, "Only the contract owner can update the metadata");
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            ContractError::Unauthorized.message("Only the contract owner can update the metadata")
+        );
+        require!(!self.metadata_locked, "Contract metadata is locked");
     
         // Update the metadata
         self.metadata = LazyOption::new(
@@ -160,13 +384,11 @@ impl Contract {
         );
     
         // Log the event
-        let event_data = json!({
-            "standard": "nep171",
-            "version": "1.1.0",
-            "event": "contract_metadata_update",
-            "data": metadata
-        });
-        env::log_str(&event_data.to_string());
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog {
+            metadata: Some(metadata),
+            ..Default::default()
+        }])
+        .emit();
     }
 
     /*
@@ -181,21 +403,44 @@ impl Contract {
         let mut approved_minters =
             LookupSet::new(StorageKey::ApprovedMinters.try_to_vec().unwrap());
         approved_minters.insert(&owner_id);
+        let mut approved_minters_list =
+            UnorderedSet::new(StorageKey::ApprovedMintersList.try_to_vec().unwrap());
+        approved_minters_list.insert(&owner_id);
 
         // Create the approved creators set and insert the owner
         let mut approved_creators =
             LookupSet::new(StorageKey::ApprovedCreators.try_to_vec().unwrap());
         approved_creators.insert(&owner_id);
+        let mut approved_creators_list =
+            UnorderedSet::new(StorageKey::ApprovedCreatorsList.try_to_vec().unwrap());
+        approved_creators_list.insert(&owner_id);
         
         
         // Create a variable of type Self with all the fields initialized.
         let this = Self {
             approved_minters,
             approved_creators,
+            approved_minters_list,
+            approved_creators_list,
             series_by_id: UnorderedMap::new(StorageKey::SeriesById.try_to_vec().unwrap()),
             //Storage keys are simply the prefixes used for the collections. This helps avoid data collision
             tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap()),
             tokens_by_id: UnorderedMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
+            //mint fees go to the owner until a treasury is set
+            treasury_id: owner_id.clone(),
+            mint_fee: 0,
+            tokens_minted: 0,
+            tokens_burned: 0,
+            credentials_by_token: LookupMap::new(StorageKey::CredentialsByToken.try_to_vec().unwrap()),
+            max_royalty_bps: 10_000,
+            disabled_operations: OperationFlags::default(),
+            recent_burns: BurnLog::new(StorageKey::RecentBurns),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits.try_to_vec().unwrap()),
+            storage_deposits_total: 0,
+            storage_payment_approvals: LookupSet::new(StorageKey::StoragePaymentApprovals.try_to_vec().unwrap()),
+            series_per_owner: LookupMap::new(StorageKey::SeriesPerOwner.try_to_vec().unwrap()),
+            swap_offers: LookupMap::new(StorageKey::SwapOffers.try_to_vec().unwrap()),
+            min_series_price: 0,
             //set the &owner_id field equal to the passed in owner_id.
             owner_id,
             metadata: LazyOption::new(
@@ -203,6 +448,15 @@ impl Contract {
                 Some(&metadata),
             ),
             allowed_transfers: UnorderedSet::new(StorageKey::AllowedTransfers.try_to_vec().unwrap()),
+            series_by_external_id: LookupMap::new(StorageKey::SeriesByExternalId.try_to_vec().unwrap()),
+            provenance_by_token: LookupMap::new(StorageKey::ProvenanceByToken.try_to_vec().unwrap()),
+            metadata_locked: false,
+            series_holdings: LookupMap::new(StorageKey::SeriesHoldings.try_to_vec().unwrap()),
+            unique_owners_by_series: LookupMap::new(StorageKey::UniqueOwnersBySeries.try_to_vec().unwrap()),
+            claim_allowances: LookupMap::new(StorageKey::ClaimAllowances.try_to_vec().unwrap()),
+            mints_by_request_id: LookupMap::new(StorageKey::MintsByRequestId.try_to_vec().unwrap()),
+            tokens_by_external_id: LookupMap::new(StorageKey::TokensByExternalId.try_to_vec().unwrap()),
+            external_id_by_token: LookupMap::new(StorageKey::ExternalIdByToken.try_to_vec().unwrap()),
         };
 
         //return the Contract object
@@ -212,85 +466,599 @@ impl Contract {
   
     //near call CONTRACT_ACCOUNT_ID new_default_meta '{"owner_id": "OWNER_ACCOUNT_ID"}' --accountId YOUR_ACCOUNT_ID
 
+    /// Replace the metadata every token of the series derives from. The `nft_metadata_update` event lists the
+    /// affected token IDs (up to `MAX_METADATA_UPDATE_TOKEN_IDS`) along with the series ID, which indexers
+    /// should use to refresh the whole series. Only the series owner can call this.
     pub fn update_series_metadata(&mut self, series_id: SeriesId, metadata: TokenMetadata) {
-        let mut series = self.series_by_id.get(&series_id).expect("Series not found");
+        assert_valid_series_metadata(&metadata);
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
         series.update_metadata(metadata);
         self.series_by_id.insert(&series_id, &series);
 
         // Emit the event
-        let event_data = json!({
-            "standard": "nep171",
-            "version": "1.1.0",
-            "event": "nft_metadata_update",
-            "data": []
-        });
-        env::log_str(&event_data.to_string());
+        let token_ids: Vec<TokenId> = series.tokens.iter().take(MAX_METADATA_UPDATE_TOKEN_IDS as usize).collect();
+        EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+            series_id: Some(series_id),
+            token_ids,
+            memo: None,
+        }])
+        .emit();
     }
 
+    /// Replace the royalty of a series. Only the series owner can call this.
     pub fn update_series_royalty(
         &mut self,
         series_id: SeriesId,
         royalty: Option<HashMap<AccountId, u32>>,
     ) {
-        let mut series = self.series_by_id.get(&series_id).expect("Series not found");
+        assert_valid_royalty(&royalty, self.max_royalty_bps);
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
         series.update_royalty(royalty);
         self.series_by_id.insert(&series_id, &series);
 
         // Emit the event
-        let event_data = json!({
-            "standard": "nep171",
-            "version": "1.1.0",
-            "event": "contract_metadata_update",
-            "data": []
-        });
-        env::log_str(&event_data.to_string());
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
     }
 
+    /// Change the price of a series. A locked series rejects any change first, then a claim-only
+    /// series rejects any change because its badges are always claimed for free. A non-zero price
+    /// can't be below the contract's minimum series price. Only the series owner can call this.
     pub fn update_series_price(&mut self, series_id: SeriesId, price: Option<Balance>) {
-        let mut series = self.series_by_id.get(&series_id).expect("Series not found");
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        require!(!series.locked, "Series is locked");
+        require!(!series.claim_only, "Claim-only series can't be priced");
+        assert_valid_price(price, self.min_series_price);
         series.update_price(price);
         self.series_by_id.insert(&series_id, &series);
 
         // Emit the event
-        let event_data = json!({
-            "standard": "nep171",
-            "version": "1.1.0",
-            "event": "contract_metadata_update",
-            "data": []
-        });
-        env::log_str(&event_data.to_string());
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
     }
 
+    /// Hand a series over to `owner_id`, leaving its royalty as is. Only the series owner can call this.
     pub fn update_series_owner_id(&mut self, series_id: SeriesId, owner_id: AccountId) {
-        let mut series = self.series_by_id.get(&series_id).expect("Series not found");
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        self.internal_move_series(series_id, &series.owner_id, &owner_id);
         series.update_owner_id(owner_id);
         self.series_by_id.insert(&series_id, &series);
 
         // Emit the event
-        let event_data = json!({
-            "standard": "nep171",
-            "version": "1.1.0",
-            "event": "contract_metadata_update",
-            "data": []
-        });
-        env::log_str(&event_data.to_string());
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
     }
 
-    
-    // Add a new function for setting allowed addresses
-    pub fn set_allowed_addresses(&mut self, addresses: Vec<AccountId>) {
-        assert_eq!(env::signer_account_id(), self.owner_id, "Only the owner can set allowed addresses");
+    /// Hand a series over to `new_owner`. With `update_royalty_recipient`, the old owner's royalty share moves to the
+    /// new owner too, keeping its basis points (added to any share the new owner already had).
+    /// Only the series owner can call this.
+    pub fn transfer_series_control(&mut self, series_id: SeriesId, new_owner: AccountId, update_royalty_recipient: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+
+        if update_royalty_recipient {
+            if let Some(royalty) = series.royalty.as_mut() {
+                if let Some(share) = royalty.remove(&series.owner_id) {
+                    *royalty.entry(new_owner.clone()).or_insert(0) += share;
+                }
+            }
+        }
+        self.internal_move_series(series_id, &series.owner_id, &new_owner);
+        series.update_owner_id(new_owner);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    pub fn update_series_description(&mut self, series_id: SeriesId, description: Option<String>) {
+        assert_max_length("Description", &description, MAX_DESCRIPTION_LENGTH);
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_description(description);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    pub fn update_series_external_url(&mut self, series_id: SeriesId, external_url: Option<String>) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_external_url(external_url);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Replace the series' banner/collection image. Only the series owner can call this.
+    pub fn update_series_collection_media(&mut self, series_id: SeriesId, collection_media: Option<String>) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_collection_media(collection_media);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    pub fn set_series_visible(&mut self, series_id: SeriesId, visible: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_visible(visible);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Mark a series as transferable so its holders can swap tokens with `nft_swap`. Only the series owner can call this.
+    pub fn set_series_transferable(&mut self, series_id: SeriesId, transferable: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_transferable(transferable);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Limit a series to one token per account, so minting to an account that already holds one panics.
+    /// Turning the limit on fails while some account already holds more than one token of the series.
+    /// Only the series owner can call this.
+    pub fn set_series_unique_per_account(&mut self, series_id: SeriesId, unique_per_account: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        if unique_per_account && !series.unique_per_account {
+            let violation = self.internal_series_holder_of_many(&series).map(|(account_id, held)| {
+                format!(
+                    "Can't limit the series to one token per account: {} already holds {} of its tokens",
+                    account_id, held
+                )
+            });
+            require!(violation.is_none(), violation.as_deref().unwrap_or_default());
+        }
+        series.update_unique_per_account(unique_per_account);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Lock a series so its price can never change again. This can't be undone. Only the series owner can call this.
+    pub fn lock_series(&mut self, series_id: SeriesId) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.lock();
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Mark a free series as claim-only so it can't be given a price later. Only the series owner can call this.
+    pub fn set_series_claim_only(&mut self, series_id: SeriesId, claim_only: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        require!(!claim_only || series.price.is_none(), "Only a free series can be claim-only");
+        series.update_claim_only(claim_only);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Set (or clear) the contract notified with `on_badge_minted` after each mint of the series.
+    /// Only the series owner can call this.
+    pub fn set_series_mint_callback(&mut self, series_id: SeriesId, on_mint_callback: Option<AccountId>) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_on_mint_callback(on_mint_callback);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Pause or resume minting for a single series without affecting the others. Only the series owner can call this.
+    pub fn set_series_minting_paused(&mut self, series_id: SeriesId, minting_paused: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_minting_paused(minting_paused);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Open (or close) the series to public minting: anyone can then `nft_mint` it, paying the price if it has one,
+    /// within the copy limit. While closed, only approved minters, the series owner and claims can mint it.
+    /// Series created with a price start open, free ones closed. Only the series owner can call this.
+    pub fn set_series_public_mint(&mut self, series_id: SeriesId, public_mint_enabled: bool) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_public_mint_enabled(public_mint_enabled);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Set when allowlisted claims of the series close (Unix epoch in milliseconds), or `None` to keep them open.
+    /// Unclaimed allowances can't be used afterwards. Only the series owner can call this.
+    pub fn set_series_claim_deadline(&mut self, series_id: SeriesId, claim_deadline: Option<u64>) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_claim_deadline(claim_deadline);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+
+    /// Restrict direct mints of the series to eligible receivers and add `account_ids` to them. Accounts already
+    /// eligible are skipped and reported. Claims with `nft_claim` aren't restricted. Only the series owner can call
+    /// this and they attach the storage for the new entries (the excess is refunded).
+    #[payable]
+    pub fn add_eligible_receivers(&mut self, series_id: SeriesId, account_ids: Vec<AccountId>) -> BatchInsertResult {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        let initial_storage_usage = env::storage_usage();
+
+        let eligible = series
+            .eligible_receivers
+            .get_or_insert_with(|| UnorderedSet::new(StorageKey::EligibleReceivers { series_id }));
+        let mut result = BatchInsertResult::default();
+        for account_id in account_ids {
+            if eligible.insert(&account_id) {
+                result.added += 1;
+            } else {
+                result.already_present += 1;
+            }
+        }
+        self.series_by_id.insert(&series_id, &series);
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        result
+    }
+
+    /// Make `account_ids` ineligible to receive direct mints of the series. The series stays restricted even when
+    /// no eligible receivers are left, use `clear_eligible_receivers` to lift the restriction.
+    /// Returns how many of the accounts were eligible. Only the series owner can call this.
+    pub fn remove_eligible_receivers(&mut self, series_id: SeriesId, account_ids: Vec<AccountId>) -> u64 {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+
+        let removed = match series.eligible_receivers.as_mut() {
+            Some(eligible) => account_ids.iter().filter(|account_id| eligible.remove(account_id)).count() as u64,
+            None => 0,
+        };
+        //the set's length is stored with the series
+        self.series_by_id.insert(&series_id, &series);
+        removed
+    }
+
+    /// Let the series be minted to anyone again, forgetting its eligible receivers. Only the series owner can call this.
+    pub fn clear_eligible_receivers(&mut self, series_id: SeriesId) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+
+        if let Some(mut eligible) = series.eligible_receivers.take() {
+            eligible.clear();
+            self.series_by_id.insert(&series_id, &series);
+        }
+    }
+
+    /// Whether `account_id` can receive direct mints of the series: always true unless the series is restricted
+    pub fn is_eligible_receiver(&self, series_id: SeriesId, account_id: AccountId) -> bool {
+        self.internal_get_series(series_id).is_eligible_receiver(&account_id)
+    }
+
+    // Add a new function for setting allowed addresses. Addresses already in the list are skipped and reported.
+    pub fn set_allowed_addresses(&mut self, addresses: Vec<AccountId>) -> BatchInsertResult {
+        require!(
+            env::signer_account_id() == self.owner_id,
+            ContractError::Unauthorized.message("Only the owner can set allowed addresses")
+        );
+        let mut result = BatchInsertResult::default();
         for address in addresses {
-            self.allowed_transfers.insert(&address);
+            //insert only writes when the address wasn't already in the set
+            if self.allowed_transfers.insert(&address) {
+                result.added += 1;
+            } else {
+                result.already_present += 1;
+            }
+        }
+        result
+    }
+
+    /// Paginate through the accounts tokens are allowed to be transferred to.
+    pub fn get_allowed_transfers(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        self.allowed_transfers
+            .iter()
+            .skip(start as usize)
+            //take the first "limit" elements in the set. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn set_allowed_addresses_counts_only_new_members() {
+        let mut contract = setup_contract();
+
+        let first = contract.set_allowed_addresses(vec![accounts(1), accounts(2)]);
+        let second = contract.set_allowed_addresses(vec![accounts(2), accounts(3)]);
+
+        assert_eq!(first, BatchInsertResult { added: 2, already_present: 0 });
+        assert_eq!(second, BatchInsertResult { added: 1, already_present: 1 });
+        assert_eq!(contract.allowed_transfers.len(), 3);
+    }
+
+    #[test]
+    fn allowed_transfers_are_paginated() {
+        let mut contract = setup_contract();
+        contract.set_allowed_addresses(vec![accounts(1), accounts(2), accounts(3), accounts(4)]);
+
+        assert_eq!(contract.get_allowed_transfers(None, Some(2)), vec![accounts(1), accounts(2)]);
+        assert_eq!(contract.get_allowed_transfers(Some(U128(2)), Some(2)), vec![accounts(3), accounts(4)]);
+        assert!(contract.get_allowed_transfers(Some(U128(4)), None).is_empty());
+        assert_eq!(contract.get_allowed_transfers(None, None).len(), 4);
+    }
+
+    #[test]
+    fn series_description_and_external_url_round_trip() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                description: Some("Awarded to early contributors".to_string()),
+                external_url: Some("https://devhub.near.page".to_string()),
+                ..series_spec(1, sample_metadata("Badge"))
+            },
+            None,
+        );
+
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.description.as_deref(), Some("Awarded to early contributors"));
+        assert_eq!(series.external_url.as_deref(), Some("https://devhub.near.page"));
+
+        contract.update_series_description(1, Some("Awarded to core contributors".to_string()));
+        contract.update_series_external_url(1, None);
+
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.description.as_deref(), Some("Awarded to core contributors"));
+        assert_eq!(series.external_url, None);
+    }
+
+    #[test]
+    fn transfer_series_control_moves_owner_and_royalty_share() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(owner(), 500), (accounts(3), 200)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+
+        contract.transfer_series_control(1, accounts(1), true);
+
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.owner_id, accounts(1));
+        assert_eq!(series.royalty, Some(HashMap::from([(accounts(1), 500), (accounts(3), 200)])));
+    }
+
+    #[test]
+    fn series_collection_media_round_trips() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mut metadata = sample_metadata("Badge");
+        metadata.media = Some("https://example.com/badge.png".to_string());
+        contract.create_series_from_spec(
+            SeriesSpec {
+                collection_media: Some("https://example.com/banner.png".to_string()),
+                ..series_spec(1, metadata)
+            },
+            None,
+        );
+
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.collection_media.as_deref(), Some("https://example.com/banner.png"));
+        assert_eq!(series.metadata.media.as_deref(), Some("https://example.com/badge.png"));
+
+        contract.update_series_collection_media(1, Some("https://example.com/banner-v2.png".to_string()));
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.collection_media.as_deref(), Some("https://example.com/banner-v2.png"));
+        assert_eq!(series.metadata.media.as_deref(), Some("https://example.com/badge.png"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the series owner can update the series")]
+    fn only_series_owner_can_update_description() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(accounts(1), 0);
+        contract.update_series_description(1, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn nft_transfer_rejects_callers_that_do_not_own_the_token() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        contract.set_allowed_addresses(vec![accounts(3)]);
+
+        set_context(accounts(2), 1);
+        contract.nft_transfer(accounts(3), token_id, None, None);
+    }
+
+    #[test]
+    fn token_owner_can_transfer_to_an_allowed_address() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        contract.set_allowed_addresses(vec![accounts(3)]);
+
+        set_context(accounts(1), 1);
+        contract.nft_transfer(accounts(3), token_id.clone(), None, None);
+
+        assert_eq!(contract.tokens_by_id.get(&token_id).unwrap().owner_id, accounts(3));
+        assert!(near_sdk::test_utils::get_logs()[0].contains(r#""event":"nft_transfer""#));
+    }
+
+    #[test]
+    fn unlocked_series_price_can_change() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        contract.update_series_price(1, Some(STORAGE_DEPOSIT));
+
+        assert_eq!(contract.series_by_id.get(&1).unwrap().price, Some(STORAGE_DEPOSIT));
+    }
+
+    #[test]
+    #[should_panic(expected = "Series is locked")]
+    fn locked_series_price_cannot_change() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.lock_series(1);
+
+        contract.update_series_price(1, Some(STORAGE_DEPOSIT));
+    }
+
+    #[test]
+    fn only_the_series_owner_can_update_the_series() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        set_context(accounts(1), 0);
+
+        let updates: [fn(&mut Contract); 4] = [
+            |contract| contract.update_series_metadata(1, sample_metadata("Hijacked")),
+            |contract| contract.update_series_royalty(1, Some(HashMap::from([(accounts(1), 5_000)]))),
+            |contract| contract.update_series_price(1, Some(STORAGE_DEPOSIT)),
+            |contract| contract.update_series_owner_id(1, accounts(1)),
+        ];
+        for update in updates {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| update(&mut contract)));
+            assert!(result.unwrap_err().downcast_ref::<String>().unwrap().contains("ERR_UNAUTHORIZED"));
         }
+
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.owner_id, owner());
+        assert_eq!(series.royalty, None);
+        assert_eq!(series.metadata.title.as_deref(), Some("Badge"));
+    }
+
+    #[test]
+    #[should_panic(expected = "A series price must be 0 or at least 1000 yoctoNEAR")]
+    fn price_below_the_minimum_is_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_min_series_price(U128(1_000));
+
+        contract.update_series_price(1, Some(1));
+    }
+
+    #[test]
+    fn zero_price_is_allowed_whatever_the_minimum() {
+        let mut contract = setup_contract();
+        contract.set_min_series_price(U128(1_000));
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(SeriesSpec { price: Some(U128(0)), ..series_spec(1, sample_metadata("Badge")) }, None);
+
+        contract.update_series_price(1, Some(0));
+        contract.update_series_price(1, Some(1_000));
+
+        assert_eq!(contract.series_by_id.get(&1).unwrap().price, Some(1_000));
     }
 
-    // Add a new function for transferring non-transferable tokens
-    pub fn transfer(&mut self, new_owner_id: AccountId, token_id: String) {
-        assert!(self.allowed_transfers.contains(&new_owner_id), "Transfer not allowed to this address");
-        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
-        token.owner_id = new_owner_id.clone();
-        self.tokens_by_id.insert(&token_id, &token);
+    #[test]
+    #[should_panic(expected = "Claim-only series can't be priced")]
+    fn claim_only_series_cannot_be_priced() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_claim_only(1, true);
+
+        contract.update_series_price(1, Some(STORAGE_DEPOSIT));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn series_metadata_update_lists_the_series_and_its_tokens() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_ids: Vec<TokenId> = (0..3).map(|_| mint_to(&mut contract, 1, accounts(1))).collect();
+
+        set_context(owner(), 0);
+        contract.update_series_metadata(1, sample_metadata("Renamed"));
+
+        let log = near_sdk::test_utils::get_logs().pop().unwrap();
+        let event: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(log.strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(event["standard"], "nep171");
+        assert_eq!(event["event"], "nft_metadata_update");
+        assert_eq!(event["data"][0]["series_id"], 1);
+        assert_eq!(event["data"][0]["token_ids"], json!(token_ids));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't limit the series to one token per account: bob already holds 2 of its tokens")]
+    fn unique_per_account_cant_be_enabled_over_existing_duplicates() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(1));
+
+        contract.set_series_unique_per_account(1, true);
+    }
+
+    #[test]
+    fn unique_per_account_can_be_enabled_when_every_holder_has_one() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(2));
+
+        contract.set_series_unique_per_account(1, true);
+
+        assert!(contract.series_by_id.get(&1).unwrap().unique_per_account);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: charlie isn't eligible to receive this series")]
+    fn restricted_series_cant_be_minted_to_ineligible_receivers() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.add_eligible_receivers(1, vec![accounts(1)]);
+
+        mint_to(&mut contract, 1, accounts(2));
+    }
+
+    #[test]
+    fn restricted_series_can_be_minted_to_eligible_receivers() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let result = contract.add_eligible_receivers(1, vec![accounts(1), accounts(2), accounts(1)]);
+        assert_eq!(result, BatchInsertResult { added: 2, already_present: 1 });
+
+        mint_to(&mut contract, 1, accounts(1));
+
+        set_context(owner(), 0);
+        assert_eq!(contract.remove_eligible_receivers(1, vec![accounts(2), accounts(3)]), 1);
+        assert!(contract.is_eligible_receiver(1, accounts(1)));
+        assert!(!contract.is_eligible_receiver(1, accounts(2)));
+        contract.clear_eligible_receivers(1);
+        assert!(contract.is_eligible_receiver(1, accounts(2)));
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+    }
+}
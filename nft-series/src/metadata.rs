@@ -7,7 +7,7 @@ pub struct Payout {
     pub payout: HashMap<AccountId, U128>,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub struct NFTContractMetadata {
     pub spec: String,              // required, essentially a version like "nft-1.0.0"
@@ -19,7 +19,7 @@ pub struct NFTContractMetadata {
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenMetadata {
     pub title: Option<String>, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
@@ -44,7 +44,36 @@ pub struct TokenMetadata {
     pub creator: Option<String>,   
 }
 
-#[derive(BorshDeserialize, BorshSerialize)]
+impl TokenMetadata {
+    //replace every field with the override's value when the override has one set
+    pub fn apply_overrides(self, overrides: TokenMetadata) -> TokenMetadata {
+        TokenMetadata {
+            title: overrides.title.or(self.title),
+            description: overrides.description.or(self.description),
+            media: overrides.media.or(self.media),
+            media_hash: overrides.media_hash.or(self.media_hash),
+            animation_url: overrides.animation_url.or(self.animation_url),
+            copies: overrides.copies.or(self.copies),
+            issued_at: overrides.issued_at.or(self.issued_at),
+            expires_at: overrides.expires_at.or(self.expires_at),
+            starts_at: overrides.starts_at.or(self.starts_at),
+            updated_at: overrides.updated_at.or(self.updated_at),
+            extra: overrides.extra.or(self.extra),
+            reference: overrides.reference.or(self.reference),
+            reference_hash: overrides.reference_hash.or(self.reference_hash),
+            achievement_type: overrides.achievement_type.or(self.achievement_type),
+            achievement_level: overrides.achievement_level.or(self.achievement_level),
+            activity_name: overrides.activity_name.or(self.activity_name),
+            completion_date: overrides.completion_date.or(self.completion_date),
+            community_event: overrides.community_event.or(self.community_event),
+            external_links: overrides.external_links.or(self.external_links),
+            creator: overrides.creator.or(self.creator),
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
 pub struct Token {
     // Series that the token belongs to
     pub series_id: u64,
@@ -54,6 +83,26 @@ pub struct Token {
     pub approved_account_ids: HashMap<AccountId, u64>,
     //the next approval ID to give out.
     pub next_approval_id: u64,
+    //per-token metadata that replaces the fields derived from the series
+    pub metadata_overrides: Option<TokenMetadata>,
+    //how much was paid for the token when it was minted (0 for free mints)
+    pub paid_amount: Balance,
+    //when the token was minted, Unix epoch in milliseconds
+    pub minted_at: u64,
+    //1-based edition number of the token within its series
+    pub edition: u64,
+    //who paid for the token's storage, refunded when it's burned. `None` for tokens minted before it was recorded
+    pub storage_charge: Option<StorageCharge>,
+}
+
+/// One owner in a token's ownership history
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProvenanceEntry {
+    //who owned the token
+    pub owner_id: AccountId,
+    //when they became the owner, Unix epoch in milliseconds
+    pub timestamp: u64,
 }
 
 //The Json token is what will be returned from view calls.
@@ -70,8 +119,29 @@ pub struct JsonToken {
     pub metadata: TokenMetadata,
     //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
     pub approved_account_ids: HashMap<AccountId, u64>,
-    //keep track of the royalty percentages for the token in a hash map
+    //the series' royalty percentages so wallets can show them without a payout preview. Left out when there are none
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub royalty: Option<HashMap<AccountId, u32>>,
+    //the issuer's external reference for the token, if it was minted with one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+}
+
+/// A standard the contract implements, as listed in the NEP-330 source metadata
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Standard {
+    pub standard: String,
+    pub version: String,
+}
+
+/// NEP-330 metadata describing the contract's source and the standards it implements
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSourceMetadata {
+    pub version: Option<String>,
+    pub link: Option<String>,
+    pub standards: Vec<Standard>,
 }
 
 pub trait NonFungibleTokenMetadata {
@@ -85,3 +155,110 @@ impl NonFungibleTokenMetadata for Contract {
         self.metadata.get().unwrap()
     }
 }
+
+#[near_bindgen]
+impl Contract {
+    /// The NEP-297 event version (`version` field) of every event this contract emits, so indexers don't have to guess
+    pub fn events_version(&self) -> String {
+        NFT_EVENTS_VERSION.to_string()
+    }
+
+    /// View the contract's version, source link and the NEPs it implements (NEP-330)
+    pub fn contract_source_metadata(&self) -> ContractSourceMetadata {
+        let standards = [
+            ("nep171", NFT_EVENTS_VERSION), // core
+            ("nep177", "2.0.0"), // metadata
+            ("nep178", "1.0.0"), // approval management
+            ("nep181", "1.0.0"), // enumeration
+            ("nep199", "2.0.0"), // royalties and payouts
+            ("nep297", "1.0.0"), // events
+            ("nep330", "1.1.0"), // source metadata
+        ];
+
+        ContractSourceMetadata {
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            link: Some("https://github.com/NEAR-DevHub/badges-contract".to_string()),
+            standards: standards
+                .iter()
+                .map(|(standard, version)| Standard {
+                    standard: standard.to_string(),
+                    version: version.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json;
+    use near_sdk::test_utils::accounts;
+    use crate::nft_core::NonFungibleTokenCore;
+    use crate::SeriesSpec;
+    use crate::test_utils::*;
+
+    #[test]
+    fn contract_source_metadata_lists_implemented_standards() {
+        let contract = setup_contract();
+
+        let source_metadata = contract.contract_source_metadata();
+        let standards: Vec<&str> = source_metadata.standards.iter().map(|s| s.standard.as_str()).collect();
+
+        assert_eq!(source_metadata.version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(standards, vec!["nep171", "nep177", "nep178", "nep181", "nep199", "nep297", "nep330"]);
+    }
+
+    #[test]
+    fn json_token_uses_nep171_field_names() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        let json = serde_json::to_value(contract.nft_token(token_id).unwrap()).unwrap();
+        let mut fields: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        fields.sort_unstable();
+
+        assert_eq!(fields, vec!["approved_account_ids", "metadata", "owner_id", "series_id", "token_id"]);
+        assert_eq!(json["token_id"], "1:1");
+        assert_eq!(json["owner_id"], accounts(1).to_string());
+        assert_eq!(json["metadata"]["title"], "Badge - 1");
+    }
+
+    #[test]
+    fn json_token_includes_the_series_royalty() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = std::collections::HashMap::from([(accounts(3), 500)]);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                royalty: Some(royalty.clone()),
+                ..series_spec(1, sample_metadata("Badge"))
+            },
+            None,
+        );
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        let token = contract.nft_token(token_id).unwrap();
+        let json = serde_json::to_value(&token).unwrap();
+
+        assert_eq!(token.royalty, Some(royalty));
+        assert_eq!(json["royalty"][accounts(3).as_str()], 500);
+    }
+
+    #[test]
+    fn emitted_events_use_the_reported_events_version() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+        let mut logs = near_sdk::test_utils::get_logs();
+        set_context(owner(), 0);
+        contract.update_series_metadata(1, sample_metadata("Renamed"));
+        logs.extend(near_sdk::test_utils::get_logs());
+
+        assert_eq!(logs.len(), 2);
+        for log in logs {
+            let event: serde_json::Value = serde_json::from_str(log.trim_start_matches("EVENT_JSON:")).unwrap();
+            assert_eq!(event["version"], contract.events_version());
+        }
+    }
+}
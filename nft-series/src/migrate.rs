@@ -0,0 +1,385 @@
+use crate::*;
+use near_sdk::collections::LookupSet;
+use std::collections::HashSet;
+
+/// The original contract layout, before external series IDs and everything after them were added
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub approved_minters: LookupSet<AccountId>,
+    pub approved_creators: LookupSet<AccountId>,
+    pub series_by_id: UnorderedMap<SeriesId, LegacySeries>,
+    pub tokens_by_id: UnorderedMap<TokenId, LegacyToken>,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub metadata: LazyOption<NFTContractMetadata>,
+    pub allowed_transfers: UnorderedSet<AccountId>,
+}
+
+/// The series layout before descriptions, sale settings and the rest of the per-series options were added
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacySeries {
+    pub metadata: TokenMetadata,
+    pub royalty: Option<HashMap<AccountId, u32>>,
+    pub tokens: UnorderedSet<TokenId>,
+    pub price: Option<Balance>,
+    pub owner_id: AccountId,
+}
+
+/// The token layout before per-token overrides and paid amounts were recorded
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyToken {
+    pub series_id: u64,
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Upgrade a deployment still using the `OldContract` layout. The transfer allowlist carries over, and approved
+    /// minters and creators keep their rights. The old sets can't be enumerated, so the list views start with just
+    /// the owner and show the others once they're re-added. Must be called by the contract account itself right after
+    /// deploying the new code, followed by `migrate_series`, `migrate_tokens`, then `index_series_owners` and
+    /// `index_series_holdings`.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read().expect("Contract is not initialized");
+
+        //the approved sets keep their prefix and entries. Only the owner can be listed up front, since lookup sets
+        //can't be enumerated: the other accounts show up in the list views once they're added again
+        let mut approved_minters_list = UnorderedSet::new(StorageKey::ApprovedMintersList.try_to_vec().unwrap());
+        approved_minters_list.insert(&old.owner_id);
+        let mut approved_creators_list = UnorderedSet::new(StorageKey::ApprovedCreatorsList.try_to_vec().unwrap());
+        approved_creators_list.insert(&old.owner_id);
+
+        Self {
+            treasury_id: old.owner_id.clone(),
+            mint_fee: 0,
+            //burns weren't counted before, so every existing token counts as minted and none as burned
+            tokens_minted: old.tokens_by_id.len(),
+            tokens_burned: 0,
+            credentials_by_token: LookupMap::new(StorageKey::CredentialsByToken.try_to_vec().unwrap()),
+            max_royalty_bps: 10_000,
+            disabled_operations: OperationFlags::default(),
+            recent_burns: BurnLog::new(StorageKey::RecentBurns),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits.try_to_vec().unwrap()),
+            storage_deposits_total: 0,
+            storage_payment_approvals: LookupSet::new(StorageKey::StoragePaymentApprovals.try_to_vec().unwrap()),
+            //filled in by `index_series_owners`
+            series_per_owner: LookupMap::new(StorageKey::SeriesPerOwner.try_to_vec().unwrap()),
+            min_series_price: 0,
+            swap_offers: LookupMap::new(StorageKey::SwapOffers.try_to_vec().unwrap()),
+            owner_id: old.owner_id,
+            approved_minters: old.approved_minters,
+            approved_creators: old.approved_creators,
+            approved_minters_list,
+            approved_creators_list,
+            //the series and tokens are rewritten into the current layout by `migrate_series` and `migrate_tokens`
+            series_by_id: UnorderedMap::try_from_slice(&old.series_by_id.try_to_vec().unwrap()).unwrap(),
+            tokens_by_id: UnorderedMap::try_from_slice(&old.tokens_by_id.try_to_vec().unwrap()).unwrap(),
+            tokens_per_owner: old.tokens_per_owner,
+            metadata: old.metadata,
+            allowed_transfers: old.allowed_transfers,
+            series_by_external_id: LookupMap::new(StorageKey::SeriesByExternalId.try_to_vec().unwrap()),
+            provenance_by_token: LookupMap::new(StorageKey::ProvenanceByToken.try_to_vec().unwrap()),
+            metadata_locked: false,
+            series_holdings: LookupMap::new(StorageKey::SeriesHoldings.try_to_vec().unwrap()),
+            unique_owners_by_series: LookupMap::new(StorageKey::UniqueOwnersBySeries.try_to_vec().unwrap()),
+            claim_allowances: LookupMap::new(StorageKey::ClaimAllowances.try_to_vec().unwrap()),
+            mints_by_request_id: LookupMap::new(StorageKey::MintsByRequestId.try_to_vec().unwrap()),
+            tokens_by_external_id: LookupMap::new(StorageKey::TokensByExternalId.try_to_vec().unwrap()),
+            external_id_by_token: LookupMap::new(StorageKey::ExternalIdByToken.try_to_vec().unwrap()),
+        }
+    }
+
+    /// Rewrite series stored in the legacy layout into the current one. Migrated series stay visible and unlocked, count
+    /// their existing tokens as minted editions, and keep the old sale rules: priced series are open sales and tokens
+    /// can be transferred to allowlisted accounts. Runs in batches to stay within the gas limit and returns how many
+    /// series were migrated. Only the contract owner can call this.
+    pub fn migrate_series(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_contract_owner();
+
+        //read the same storage through the legacy layout, as `migrate_tokens` does
+        let legacy_series: UnorderedMap<SeriesId, LegacySeries> =
+            UnorderedMap::try_from_slice(&self.series_by_id.try_to_vec().unwrap()).unwrap();
+        let series_ids: Vec<SeriesId> = legacy_series
+            .keys()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect();
+
+        let values_prefix = [StorageKey::SeriesById.try_to_vec().unwrap(), b"v".to_vec()].concat();
+        for (offset, series_id) in series_ids.iter().enumerate() {
+            let legacy = legacy_series.get(series_id).unwrap();
+            let series = Series {
+                metadata: legacy.metadata,
+                royalty: legacy.royalty,
+                //tokens were never removed from a series before, so every edition so far is still in the set
+                editions_minted: legacy.tokens.len(),
+                tokens: legacy.tokens,
+                public_mint_enabled: legacy.price.is_some(),
+                price: legacy.price,
+                owner_id: legacy.owner_id,
+                description: None,
+                external_url: None,
+                visible: true,
+                revenue: 0,
+                transferable: false,
+                unique_per_account: false,
+                locked: false,
+                claim_only: false,
+                on_mint_callback: None,
+                minting_paused: false,
+                collection_media: None,
+                claim_deadline: None,
+                eligible_receivers: None,
+                storage_payer: StoragePayer::default(),
+            };
+            let index = from_index + offset as u64;
+            let value_key = [values_prefix.as_slice(), &index.to_le_bytes()].concat();
+            env::storage_write(&value_key, &series.try_to_vec().unwrap());
+        }
+
+        series_ids.len() as u64
+    }
+
+    /// Rewrite tokens stored in the legacy layout into the current one, defaulting the new fields
+    /// (no metadata overrides, a paid amount of 0, an unknown mint time of 0 and no recorded storage payer, so burning
+    /// them refunds no storage). The edition is recovered from the
+    /// `${series_id}:${edition}` token ID. Runs in batches to stay within the gas limit and
+    /// returns how many tokens were migrated. Only the contract owner can call this.
+    pub fn migrate_tokens(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_contract_owner();
+
+        //read the same storage through the legacy layout (the map's own state only holds prefixes and lengths)
+        let legacy_tokens: UnorderedMap<TokenId, LegacyToken> =
+            UnorderedMap::try_from_slice(&self.tokens_by_id.try_to_vec().unwrap()).unwrap();
+        let token_ids: Vec<TokenId> = legacy_tokens
+            .keys()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect();
+
+        //the map stores values in a vector under `${prefix}v`, indexed in the same order as its keys
+        let values_prefix = [StorageKey::TokensById.try_to_vec().unwrap(), b"v".to_vec()].concat();
+        for (offset, token_id) in token_ids.iter().enumerate() {
+            let legacy = legacy_tokens.get(token_id).unwrap();
+            let token = Token {
+                series_id: legacy.series_id,
+                owner_id: legacy.owner_id,
+                approved_account_ids: legacy.approved_account_ids,
+                next_approval_id: legacy.next_approval_id,
+                metadata_overrides: None,
+                paid_amount: 0,
+                minted_at: 0,
+                edition: token_id.rsplit(':').next().and_then(|edition| edition.parse().ok()).unwrap_or(0),
+                storage_charge: None,
+            };
+            //write the value in place. Inserting through the map would try to read the old value in the new layout.
+            let index = from_index + offset as u64;
+            let value_key = [values_prefix.as_slice(), &index.to_le_bytes()].concat();
+            env::storage_write(&value_key, &token.try_to_vec().unwrap());
+        }
+
+        token_ids.len() as u64
+    }
+
+    /// Add up to `limit` series, starting at `from_index`, to their owner's entry in the per-owner series index
+    /// that `managed_series` reads. Series created before the index existed aren't in it until this runs over them.
+    /// Series still in the legacy layout must be rewritten with `migrate_series` first.
+    /// Indexing a series twice is harmless. Returns how many series were visited. Only the contract owner can call this.
+    pub fn index_series_owners(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_contract_owner();
+
+        let series: Vec<(SeriesId, AccountId)> = self
+            .series_by_id
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|(series_id, series)| (series_id, series.owner_id))
+            .collect();
+        for (series_id, owner_id) in series.iter() {
+            self.internal_add_series_to_owner(owner_id, *series_id);
+        }
+
+        series.len() as u64
+    }
+
+    /// Recount the series holdings of the owners of up to `limit` tokens, starting at `from_index` (in `nft_tokens`
+    /// order), from the tokens they hold. Tokens held since before the counts were kept aren't in them until this
+    /// runs over them, so one-per-account checks, `holders_check` and `series_unique_owners` would miss them.
+    /// Recounting is idempotent, so batches may overlap. Returns how many tokens were visited.
+    /// Only the contract owner can call this.
+    pub fn index_series_holdings(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_contract_owner();
+
+        let token_ids = self.tokens_by_id.keys_as_vector();
+        let start = from_index.min(token_ids.len());
+        let end = start.saturating_add(limit).min(token_ids.len());
+        let owners: HashSet<AccountId> = (start..end)
+            .filter_map(|index| token_ids.get(index))
+            .map(|token_id| self.tokens_by_id.get(&token_id).unwrap().owner_id)
+            .collect();
+        for owner_id in owners.iter() {
+            self.internal_recount_series_holdings(owner_id, HashSet::new());
+        }
+
+        end - start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn migrate_tokens_defaults_paid_amount_to_zero() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let mut legacy_tokens: UnorderedMap<TokenId, LegacyToken> =
+            UnorderedMap::try_from_slice(&contract.tokens_by_id.try_to_vec().unwrap()).unwrap();
+        legacy_tokens.insert(
+            &"1:1".to_string(),
+            &LegacyToken {
+                series_id: 1,
+                owner_id: accounts(1),
+                approved_account_ids: Default::default(),
+                next_approval_id: 0,
+            },
+        );
+        contract.tokens_by_id = UnorderedMap::try_from_slice(&legacy_tokens.try_to_vec().unwrap()).unwrap();
+
+        set_context(owner(), 0);
+        assert_eq!(contract.migrate_tokens(0, 10), 1);
+
+        let token = contract.tokens_by_id.get(&"1:1".to_string()).unwrap();
+        assert_eq!(token.owner_id, accounts(1));
+        assert_eq!(token.paid_amount, 0);
+        assert_eq!(token.edition, 1);
+        assert!(token.metadata_overrides.is_none());
+        assert!(token.storage_charge.is_none());
+    }
+
+    #[test]
+    fn migrate_carries_over_the_allowlist_and_approved_sets() {
+        set_context(owner(), 0);
+        let metadata = NFTContractMetadata {
+            spec: "nft-1.0.0".to_string(),
+            name: "Old Badges".to_string(),
+            symbol: "BADGE".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        };
+        let mut approved_minters = LookupSet::new(StorageKey::ApprovedMinters.try_to_vec().unwrap());
+        approved_minters.insert(&owner());
+        approved_minters.insert(&accounts(1));
+        let mut allowed_transfers = UnorderedSet::new(StorageKey::AllowedTransfers.try_to_vec().unwrap());
+        allowed_transfers.insert(&accounts(2));
+        let old = OldContract {
+            owner_id: owner(),
+            approved_minters,
+            approved_creators: LookupSet::new(StorageKey::ApprovedCreators.try_to_vec().unwrap()),
+            series_by_id: UnorderedMap::new(StorageKey::SeriesById.try_to_vec().unwrap()),
+            tokens_by_id: UnorderedMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
+            tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap()),
+            metadata: LazyOption::new(StorageKey::NFTContractMetadata.try_to_vec().unwrap(), Some(&metadata)),
+            allowed_transfers,
+        };
+        env::state_write(&old);
+
+        set_context("badges.near".parse().unwrap(), 0);
+        let mut migrated = Contract::migrate();
+
+        assert_eq!(migrated.owner_id, owner());
+        assert_eq!(migrated.get_allowed_transfers(None, None), vec![accounts(2)]);
+        assert_eq!(migrated.get_approved_minters(), vec![owner()]);
+        assert_eq!(migrated.nft_metadata().name, "Old Badges");
+        //the earlier minter keeps its rights, and shows up in the list once it's re-added
+        assert!(migrated.is_approved_minter(accounts(1)));
+        set_context(owner(), 0);
+        assert!(!migrated.add_approved_minter(accounts(1)));
+        assert_eq!(migrated.get_approved_minters(), vec![owner(), accounts(1)]);
+    }
+
+    //load contract state written by the original contract code: alice owns the contract, bob is an approved minter,
+    //charlie an approved creator who made series 1 (10 copies, 5% royalty to charlie), bob minted 1:1 to danny and
+    //1:2 to eugene, danny approved fargo on 1:1, and eugene is the only allowlisted account
+    fn load_baseline_state() {
+        set_context("badges.near".parse().unwrap(), 0);
+        let decode = |hex: &str| {
+            (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect::<Vec<u8>>()
+        };
+        for line in include_str!("../tests/fixtures/baseline_state.hex").lines() {
+            let (key, value) = line.split_once(' ').unwrap();
+            env::storage_write(&decode(key), &decode(value));
+        }
+    }
+
+    #[test]
+    fn migrating_baseline_state_keeps_series_tokens_and_roles() {
+        load_baseline_state();
+        let mut contract = Contract::migrate();
+        set_context(owner(), 0);
+        assert_eq!(contract.migrate_series(0, 10), 1);
+        assert_eq!(contract.migrate_tokens(0, 10), 2);
+        assert_eq!(contract.index_series_owners(0, 10), 1);
+        assert_eq!(contract.index_series_holdings(0, 10), 2);
+
+        assert_eq!(contract.get_allowed_transfers(None, None), vec![accounts(4)]);
+        assert!(contract.is_approved_minter(accounts(1)));
+        assert!(contract.is_approved_creator(accounts(2)));
+
+        let series = contract.get_series_details(1).unwrap();
+        assert_eq!(series.owner_id, accounts(2));
+        assert_eq!(series.metadata.title, Some("Contributor".to_string()));
+        assert_eq!(series.metadata.copies, Some(10));
+        assert_eq!(series.royalty.unwrap().get(&accounts(2)), Some(&500));
+        assert_eq!(contract.managed_series(accounts(2)).series_ids, vec![1]);
+
+        let token = contract.nft_token("1:1".to_string()).unwrap();
+        assert_eq!(token.owner_id, accounts(3));
+        assert_eq!(token.approved_account_ids.get(&accounts(5)), Some(&0));
+        assert_eq!(contract.nft_supply_for_owner(accounts(4)).0, 1);
+        assert_eq!(contract.series_unique_owners(1), 2);
+        assert_eq!(contract.holders_check(1, vec![accounts(3), accounts(1)]), vec![true, false]);
+
+        //the next mint continues the edition numbering
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(3), None, None, None, None);
+        assert!(contract.nft_token("1:3".to_string()).is_some());
+
+        //allowlisted transfers keep working as before
+        set_context(accounts(3), 1);
+        contract.nft_transfer(accounts(4), "1:1".to_string(), None, None);
+        assert_eq!(contract.nft_token("1:1".to_string()).unwrap().owner_id, accounts(4));
+    }
+
+    #[test]
+    fn indexing_series_holdings_counts_uncounted_tokens_once() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(2));
+        //forget the counts, as for tokens minted before they were kept
+        contract.series_holdings.remove(&(1, accounts(1)));
+        contract.series_holdings.remove(&(1, accounts(2)));
+        contract.unique_owners_by_series.remove(&1);
+        assert_eq!(contract.holders_check(1, vec![accounts(1)]), vec![false]);
+
+        set_context(owner(), 0);
+        assert_eq!(contract.index_series_holdings(0, 2), 2);
+        assert_eq!(contract.index_series_holdings(1, 10), 2);
+        assert_eq!(contract.index_series_holdings(3, 10), 0);
+
+        assert_eq!(contract.holders_check(1, vec![accounts(1), accounts(2), accounts(3)]), vec![true, true, false]);
+        assert_eq!(contract.series_unique_owners(1), 2);
+        assert_eq!(contract.series_holdings.get(&(1, accounts(1))), Some(2));
+    }
+}
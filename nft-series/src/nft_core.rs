@@ -3,6 +3,10 @@ use near_sdk::{ext_contract, Gas, PromiseResult};
 
 const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(10_000_000_000_000);
 const GAS_FOR_NFT_ON_TRANSFER: Gas = Gas(25_000_000_000_000);
+//minimum gas nft_transfer_call needs: the receiver's nft_on_transfer, our nft_resolve_transfer and the transfer itself
+const MIN_GAS_FOR_NFT_TRANSFER_CALL: Gas = Gas(
+    GAS_FOR_NFT_ON_TRANSFER.0 + GAS_FOR_RESOLVE_TRANSFER.0 + 10_000_000_000_000,
+);
 
 pub trait NonFungibleTokenCore {
     //transfers an NFT to a receiver ID
@@ -16,6 +20,9 @@ pub trait NonFungibleTokenCore {
     );
 
     //transfers an NFT to a receiver and calls a function on the receiver ID's contract
+    /// `msg` is passed to the receiver's `nft_on_transfer` exactly as given, so contracts like escrows can embed
+    /// their own instructions (e.g. a JSON payload). This contract never parses or rewrites it. An approved account
+    /// can make the call for the owner by passing its `approval_id`.
     /// Returns `true` if the token was transferred from the sender's account.
     fn nft_transfer_call(
         &mut self,
@@ -31,6 +38,8 @@ pub trait NonFungibleTokenCore {
     fn nft_token(&self, token_id: TokenId) -> Option<JsonToken>;
 }
 
+// only the generated `ext_non_fungible_token_receiver` module is called, never the trait itself
+#[allow(dead_code)]
 #[ext_contract(ext_non_fungible_token_receiver)]
 trait NonFungibleTokenReceiver {
     //Method stored on the receiver contract that is called via cross contract call when nft_transfer_call is called
@@ -44,6 +53,8 @@ trait NonFungibleTokenReceiver {
     ) -> Promise;
 }
 
+// only the generated `ext_self` module is called, never the trait itself
+#[allow(dead_code)]
 #[ext_contract(ext_self)]
 /*
     resolves the promise of the cross contract call to the receiver contract
@@ -81,7 +92,6 @@ impl NonFungibleTokenCore for Contract {
         assert_one_yocto();
         //get the sender to transfer the token from the sender to the receiver
         let sender_id = env::predecessor_account_id();
-        assert!(self.allowed_transfers.contains(&receiver_id), "Transfer not allowed to this address");
         //call the internal transfer method and get back the previous token so we can refund the approved account IDs
         let previous_token =
             self.internal_transfer(&sender_id, &receiver_id, &token_id, approval_id, memo);
@@ -107,9 +117,18 @@ impl NonFungibleTokenCore for Contract {
         //assert that the user attached exactly 1 yocto for security reasons.
         assert_one_yocto();
 
+        //make sure there's enough gas to finish the cross contract call and resolve it before changing any state.
+        //otherwise the token could be transferred without nft_resolve_transfer ever running.
+        require!(
+            env::prepaid_gas() >= MIN_GAS_FOR_NFT_TRANSFER_CALL,
+            format!(
+                "nft_transfer_call requires at least {} gas to be attached",
+                MIN_GAS_FOR_NFT_TRANSFER_CALL.0
+            )
+        );
+
         //get the sender ID
         let sender_id = env::predecessor_account_id();
-        assert!(self.allowed_transfers.contains(&receiver_id), "Transfer not allowed to this address");
         //transfer the token and get the previous token object
         let previous_token = self.internal_transfer(
             &sender_id,
@@ -158,24 +177,34 @@ impl NonFungibleTokenCore for Contract {
         //if there is some token ID in the tokens_by_id collection
         if let Some(token) = self.tokens_by_id.get(&token_id) {
             // Get the series information
-            let cur_series = self
-                .series_by_id
-                .get(&token.series_id)
-                .expect("Not a series");
+            let cur_series = self.internal_get_series(token.series_id);
             // Get the metadata for the series
             let mut metadata = cur_series.metadata;
 
-            // Get the edition number and series ID
-            let split: Vec<&str> = token_id.split(":").collect();
-            let edition_number = split[1];
+            // Get the edition number recorded at mint (the token ID keeps the series it was first minted in)
+            let edition_number = token.edition;
             // If there is a title for the NFT, add the token ID to it.
             if let Some(title) = metadata.title {
                 metadata.title = Some(format!("{} - {}", title, edition_number));
             } else {
                 // If there is no title, we simply create one based on the series number and edition number
-                metadata.title = Some(format!("Series {} : Edition {}", split[0], split[1]));
+                metadata.title = Some(format!("Series {} : Edition {}", token.series_id, edition_number));
             }
 
+            // Per-token overrides take precedence over everything derived from the series
+            if let Some(overrides) = token.metadata_overrides {
+                metadata = metadata.apply_overrides(overrides);
+            }
+
+            let external_id = self.external_id_by_token.get(&token_id);
+
+            // Surface the per-token data recorded at mint in the metadata's extra JSON
+            let mut extra_entries = vec![("paid_amount", json!(U128(token.paid_amount)))];
+            if let Some(credential) = self.credentials_by_token.get(&token_id) {
+                extra_entries.push(("credential", near_sdk::serde_json::from_str(&credential).unwrap_or_default()));
+            }
+            metadata.extra = Some(token_extra(metadata.extra, extra_entries));
+
             //we return the JsonToken (wrapped by Some since we return an option)
             Some(JsonToken {
                 series_id: token.series_id,
@@ -184,6 +213,7 @@ impl NonFungibleTokenCore for Contract {
                 metadata,
                 approved_account_ids: token.approved_account_ids,
                 royalty: cur_series.royalty,
+                external_id,
             })
         } else {
             //if there wasn't a token ID in the tokens_by_id collection, we return None
@@ -195,7 +225,10 @@ impl NonFungibleTokenCore for Contract {
 #[near_bindgen]
 impl NonFungibleTokenResolver for Contract {
     //resolves the cross contract call when calling nft_on_transfer in the nft_transfer_call method
-    //returns true if the token was successfully transferred to the receiver_id
+    //returns true if the token was successfully transferred to the receiver_id.
+    //if the receiver returns the token, ownership, the owner sets in tokens_per_owner and the approvals are all
+    //restored in this single call. The original owner's approval storage is still in use so only the receiver,
+    //whose approvals are dropped, gets a storage refund.
     #[private]
     fn nft_resolve_transfer(
         &mut self,
@@ -244,9 +277,9 @@ impl NonFungibleTokenResolver for Contract {
         };
 
         //we remove the token from the receiver
-        self.internal_remove_token_from_owner(&receiver_id.clone(), &token_id);
+        self.internal_remove_token_from_owner(&receiver_id.clone(), &token_id, token.series_id);
         //we add the token to the original owner
-        self.internal_add_token_to_owner(&owner_id, &token_id);
+        self.internal_add_token_to_owner(&owner_id, &token_id, token.series_id);
 
         //we change the token struct's owner to be the original owner
         token.owner_id = owner_id.clone();
@@ -258,36 +291,181 @@ impl NonFungibleTokenResolver for Contract {
 
         //we inset the token back into the tokens_by_id collection
         self.tokens_by_id.insert(&token_id, &token);
+        self.internal_record_owner(&token_id, &owner_id);
 
         /*
             We need to log that the NFT was reverted back to the original owner.
             The old_owner_id will be the receiver and the new_owner_id will be the
             original owner of the token since we're reverting the transfer.
         */
-        let nft_transfer_log: EventLog = EventLog {
-            // Standard name ("nep171").
-            standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
-            version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
-            event: EventLogVariant::NftTransfer(vec![NftTransferLog {
-                // The optional authorized account ID to transfer the token on behalf of the old owner.
-                authorized_id,
-                // The old owner's account ID.
-                old_owner_id: receiver_id.to_string(),
-                // The account ID of the new owner of the token.
-                new_owner_id: owner_id.to_string(),
-                // A vector containing the token IDs as strings.
-                token_ids: vec![token_id.to_string()],
-                // An optional memo to include.
-                memo,
-            }]),
-        };
-
-        //we perform the actual logging
-        env::log_str(&nft_transfer_log.to_string());
+        EventLogVariant::NftTransfer(vec![NftTransferLog {
+            // The optional authorized account ID to transfer the token on behalf of the old owner.
+            authorized_id,
+            // The old owner's account ID.
+            old_owner_id: receiver_id.to_string(),
+            // The account ID of the new owner of the token.
+            new_owner_id: owner_id.to_string(),
+            // A vector containing the token IDs as strings.
+            token_ids: vec![token_id.to_string()],
+            // An optional memo to include.
+            memo,
+        }]).emit();
 
         //return false
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::testing_env;
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::{accounts, get_logs};
+
+    fn setup_with_token() -> (Contract, TokenId) {
+        let mut contract = setup_contract();
+        contract.set_allowed_addresses(vec![accounts(2)]);
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        (contract, token_id)
+    }
+
+    #[test]
+    fn owner_transfer_ignores_approval_id() {
+        let (mut contract, token_id) = setup_with_token();
+
+        set_context(accounts(1), 1);
+        contract.nft_transfer(accounts(2), token_id.clone(), Some(42), None);
+
+        assert_eq!(contract.tokens_by_id.get(&token_id).unwrap().owner_id, accounts(2));
+        assert!(!get_logs().iter().any(|log| log.contains("authorized_id")));
+    }
+
+    #[test]
+    fn approved_transfer_validates_approval_id() {
+        let (mut contract, token_id) = setup_with_token();
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id.clone(), accounts(3), None);
+
+        set_context(accounts(3), 1);
+        contract.nft_transfer(accounts(2), token_id.clone(), Some(0), None);
+
+        assert_eq!(contract.tokens_by_id.get(&token_id).unwrap().owner_id, accounts(2));
+        assert!(get_logs().iter().any(|log| log.contains(r#""authorized_id":"danny""#)));
+    }
+
+    #[test]
+    #[should_panic(expected = "The actual approval_id 0 is different from the given approval_id 7")]
+    fn approved_transfer_rejects_wrong_approval_id() {
+        let (mut contract, token_id) = setup_with_token();
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id.clone(), accounts(3), None);
+
+        set_context(accounts(3), 1);
+        contract.nft_transfer(accounts(2), token_id, Some(7), None);
+    }
+
+    #[test]
+    fn bounced_transfer_call_restores_owner_and_approvals() {
+        let (mut contract, token_id) = setup_with_token();
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id.clone(), accounts(3), None);
+
+        set_context(accounts(1), 1);
+        contract.nft_transfer_call(accounts(2), token_id.clone(), None, None, "".to_string());
+        let previous_approvals = HashMap::from([(accounts(3), 0)]);
+
+        // The receiver's nft_on_transfer asks for the token back
+        set_callback_context(PromiseResult::Successful(b"true".to_vec()));
+        let transferred = contract.nft_resolve_transfer(
+            None,
+            accounts(1),
+            accounts(2),
+            token_id.clone(),
+            previous_approvals.clone(),
+            None,
+        );
+
+        assert!(!transferred);
+        let token = contract.tokens_by_id.get(&token_id).unwrap();
+        assert_eq!(token.owner_id, accounts(1));
+        assert_eq!(token.approved_account_ids, previous_approvals);
+        assert!(contract.tokens_per_owner.get(&accounts(1)).unwrap().contains(&token_id));
+        assert!(contract.tokens_per_owner.get(&accounts(2)).is_none());
+    }
+
+    #[test]
+    fn under_gassed_transfer_call_is_rejected_before_any_state_change() {
+        let (mut contract, token_id) = setup_with_token();
+
+        let mut context = set_context(accounts(1), 1);
+        testing_env!(context.prepaid_gas(MIN_GAS_FOR_NFT_TRANSFER_CALL - Gas(1)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer_call(accounts(2), token_id.clone(), None, None, "".to_string())
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(contract.tokens_by_id.get(&token_id).unwrap().owner_id, accounts(1));
+        assert!(contract.tokens_per_owner.get(&accounts(2)).is_none());
+    }
+
+    #[test]
+    fn transfer_call_forwards_msg_verbatim() {
+        let (mut contract, token_id) = setup_with_token();
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id.clone(), accounts(2), None);
+        let msg = r#"{"action":"escrow","release_to":"carol.near","note":"héllo \"quoted\" \u0000"}"#.to_string();
+
+        // The approved escrow pulls the token into itself with its instructions attached
+        set_context(accounts(2), 1);
+        contract.nft_transfer_call(accounts(2), token_id.clone(), Some(0), None, msg.clone());
+
+        let receipt = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .find(|receipt| receipt.receiver_id == accounts(2))
+            .unwrap();
+        match &receipt.actions[0] {
+            near_sdk::mock::VmAction::FunctionCall { function_name, args, .. } => {
+                let args: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(args).unwrap();
+                assert_eq!(function_name, "nft_on_transfer");
+                assert_eq!(args["msg"].as_str().unwrap().as_bytes(), msg.as_bytes());
+                assert_eq!(args["sender_id"], accounts(2).to_string());
+                assert_eq!(args["previous_owner_id"], accounts(1).to_string());
+            }
+            action => panic!("unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn credential_round_trips_into_the_token_extra() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let credential = r#"{"issuer":"did:near:devhub.near","skill":"Rust","score":92}"#;
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let token_id = contract.nft_mint(
+            near_sdk::json_types::U64(1),
+            accounts(1),
+            None,
+            None,
+            None,
+            Some(credential.to_string()),
+        );
+
+        let extra = contract.nft_token(token_id).unwrap().metadata.extra.unwrap();
+        let extra: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(&extra).unwrap();
+        assert_eq!(extra["credential"], near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(credential).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Credential can be at most 2000 characters long")]
+    fn over_long_credentials_are_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let credential = format!(r#"{{"claim":"{}"}}"#, "x".repeat(2000));
+        contract.nft_mint(near_sdk::json_types::U64(1), accounts(1), None, None, None, Some(credential));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::*;
+
+/// A class of operation the contract owner can switch off on its own during an incident
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[serde(crate = "near_sdk::serde")]
+pub enum Operation {
+    Mint,
+    Transfer,
+    Approve,
+    Burn,
+}
+
+impl Operation {
+    const fn bit(self) -> u8 {
+        match self {
+            Operation::Mint => 1,
+            Operation::Transfer => 1 << 1,
+            Operation::Approve => 1 << 2,
+            Operation::Burn => 1 << 3,
+        }
+    }
+}
+
+/// The operations currently disabled, one bit per `Operation`. Empty means everything is enabled.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperationFlags(u8);
+
+impl OperationFlags {
+    pub fn is_disabled(&self, op: Operation) -> bool {
+        self.0 & op.bit() != 0
+    }
+
+    pub fn set_disabled(&mut self, op: Operation, disabled: bool) {
+        if disabled {
+            self.0 |= op.bit();
+        } else {
+            self.0 &= !op.bit();
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Enable or disable one class of operation (`mint`, `transfer`, `approve` or `burn`) without touching the others.
+    /// Disabling `transfer` is the same switch `set_transfers_enabled` flips.
+    /// Only the contract owner can call this.
+    pub fn set_operation_enabled(&mut self, op: Operation, enabled: bool) {
+        self.assert_contract_owner();
+        self.disabled_operations.set_disabled(op, !enabled);
+    }
+
+    /// Whether the given class of operation is currently enabled
+    pub fn is_operation_enabled(&self, op: Operation) -> bool {
+        !self.disabled_operations.is_disabled(op)
+    }
+}
+
+impl Contract {
+    //panics if the contract owner disabled this class of operation
+    pub(crate) fn assert_operation_enabled(&self, op: Operation) {
+        if self.disabled_operations.is_disabled(op) {
+            ContractError::Paused.panic(&format!("{:?} is disabled", op));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn disabling_transfers_leaves_minting_working() {
+        let mut contract = setup_contract();
+        contract.set_allowed_addresses(vec![accounts(2)]);
+        create_sample_series(&mut contract, 1);
+        contract.set_operation_enabled(Operation::Transfer, false);
+
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        assert!(!contract.is_operation_enabled(Operation::Transfer));
+        assert!(contract.is_operation_enabled(Operation::Mint));
+
+        set_context(accounts(1), 1);
+        let transfer = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(accounts(2), token_id.clone(), None, None);
+        }));
+        assert!(transfer.unwrap_err().downcast_ref::<String>().unwrap().contains("ERR_PAUSED: Transfer is disabled"));
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+    }
+
+    #[test]
+    fn disabling_minting_leaves_transfers_working() {
+        let mut contract = setup_contract();
+        contract.set_allowed_addresses(vec![accounts(2)]);
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        set_context(owner(), 0);
+        contract.set_operation_enabled(Operation::Mint, false);
+
+        let mint = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mint_to(&mut contract, 1, accounts(2));
+        }));
+        assert!(mint.unwrap_err().downcast_ref::<String>().unwrap().contains("ERR_PAUSED: Mint is disabled"));
+
+        set_context(accounts(1), 1);
+        contract.nft_transfer(accounts(2), token_id.clone(), None, None);
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(2));
+    }
+
+    #[test]
+    fn reenabling_an_operation_restores_it() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_operation_enabled(Operation::Mint, false);
+        contract.set_operation_enabled(Operation::Burn, false);
+        contract.set_operation_enabled(Operation::Mint, true);
+
+        mint_to(&mut contract, 1, accounts(1));
+        assert!(!contract.is_operation_enabled(Operation::Burn));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Burn is disabled")]
+    fn disabled_burns_are_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        set_context(owner(), 0);
+        contract.set_operation_enabled(Operation::Burn, false);
+
+        set_context(accounts(1), 1);
+        contract.nft_batch_burn(vec![token_id]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Approve is disabled")]
+    fn disabled_approvals_are_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        set_context(owner(), 0);
+        contract.set_operation_enabled(Operation::Approve, false);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_approve(token_id, accounts(2), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED")]
+    fn only_the_owner_can_disable_operations() {
+        let mut contract = setup_contract();
+
+        set_context(accounts(1), 0);
+        contract.set_operation_enabled(Operation::Mint, false);
+    }
+}
@@ -1,17 +1,38 @@
 use crate::*;
+use std::collections::{BTreeMap, HashSet};
+
+/// The contract's storage footprint and what it costs to keep
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageStats {
+    // Bytes of state the contract account uses
+    pub storage_usage: u64,
+    // Balance that has to stay on the account to pay for that storage
+    pub storage_cost_yocto: U128,
+    // Balance locked for staking, which doesn't count towards paying for storage
+    pub locked_balance: U128,
+}
 
 #[near_bindgen]
 impl Contract {
-    /// Add a specified account as an approved minter
-    pub fn add_approved_minter(&mut self, account_id: AccountId) {
+    /// The contract owner, who manages minters, creators and contract-wide settings
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Add a specified account as an approved minter. Returns `false` if it already was one, so retries are safe.
+    /// Re-adding an account approved before the list views existed makes it show up in them.
+    pub fn add_approved_minter(&mut self, account_id: AccountId) -> bool {
         self.assert_contract_owner();
-        self.approved_minters.insert(&account_id);
+        self.approved_minters_list.insert(&account_id);
+        self.approved_minters.insert(&account_id)
     }
 
-    /// Remove a specified account as an approved minter
-    pub fn remove_approved_minter(&mut self, account_id: AccountId) {
+    /// Remove a specified account as an approved minter. Returns `false` if it wasn't one, so retries are safe.
+    pub fn remove_approved_minter(&mut self, account_id: AccountId) -> bool {
         self.assert_contract_owner();
-        self.approved_minters.remove(&account_id);
+        self.approved_minters_list.remove(&account_id);
+        self.approved_minters.remove(&account_id)
     }
 
     /// Check if a specified account is an approved minter
@@ -19,20 +40,513 @@ impl Contract {
         self.approved_minters.contains(&account_id)
     }
 
-    /// Add a specified account as an approved creator
-    pub fn add_approved_creator(&mut self, account_id: AccountId) {
+    /// Add a specified account as an approved creator. Returns `false` if it already was one, so retries are safe.
+    /// Re-adding an account approved before the list views existed makes it show up in them.
+    pub fn add_approved_creator(&mut self, account_id: AccountId) -> bool {
         self.assert_contract_owner();
-        self.approved_creators.insert(&account_id);
+        self.approved_creators_list.insert(&account_id);
+        self.approved_creators.insert(&account_id)
     }
 
-    /// Remove a specified account as an approved creator
-    pub fn remove_approved_creator(&mut self, account_id: AccountId) {
+    /// Remove a specified account as an approved creator. Returns `false` if it wasn't one, so retries are safe.
+    pub fn remove_approved_creator(&mut self, account_id: AccountId) -> bool {
         self.assert_contract_owner();
-        self.approved_creators.remove(&account_id);
+        self.approved_creators_list.remove(&account_id);
+        self.approved_creators.remove(&account_id)
     }
 
     /// Check if a specified account is an approved creator
     pub fn is_approved_creator(&self, account_id: AccountId) -> bool {
         self.approved_creators.contains(&account_id)
     }
+
+    /// Check if a specified account can create series: the contract owner or an approved creator.
+    /// Lets onboarding UIs decide whether to offer series creation.
+    pub fn can_create_series(&self, account_id: AccountId) -> bool {
+        self.internal_can_create_series(&account_id)
+    }
+
+    /// List all the approved minters. On an upgraded deployment, minters approved before the list existed only
+    /// show up once they're added again (they keep minting either way).
+    pub fn get_approved_minters(&self) -> Vec<AccountId> {
+        self.approved_minters_list.to_vec()
+    }
+
+    /// List all the approved creators. On an upgraded deployment, creators approved before the list existed only
+    /// show up once they're added again (they keep creating series either way).
+    pub fn get_approved_creators(&self) -> Vec<AccountId> {
+        self.approved_creators_list.to_vec()
+    }
+
+    /// Withdraw NEAR that accumulated on the contract beyond what it needs to stake for storage
+    /// (e.g. deposits attached to calls that didn't need them). Accounts' storage deposits are theirs and are never
+    /// part of the surplus. Only the contract owner can call this.
+    pub fn owner_withdraw_surplus(&mut self, amount: U128) -> Promise {
+        self.assert_contract_owner();
+
+        //the balance needed to cover the contract's current storage can never be withdrawn, and neither can the
+        //storage deposits accounts set aside for future mints
+        let storage_reserve = Balance::from(env::storage_usage()) * env::storage_byte_cost();
+        let surplus = env::account_balance()
+            .saturating_sub(storage_reserve)
+            .saturating_sub(self.storage_deposits_total);
+        require!(
+            amount.0 <= surplus,
+            format!("Can only withdraw up to {} yoctoNEAR of surplus", surplus)
+        );
+
+        Promise::new(self.owner_id.clone()).transfer(amount.0)
+    }
+
+    /// Report how much storage the contract uses and the balance it must keep to cover it,
+    /// so operators can top the account up before it runs short.
+    pub fn storage_stats(&self) -> StorageStats {
+        let storage_usage = env::storage_usage();
+        StorageStats {
+            storage_usage,
+            storage_cost_yocto: U128(Balance::from(storage_usage) * env::storage_byte_cost()),
+            locked_balance: U128(env::account_locked_balance()),
+        }
+    }
+
+    /// Freeze (or unfreeze) every transfer, swap and transfer call while keeping minting open.
+    /// `admin_transfer` still works so the owner can fix holdings during a freeze. This is a shortcut for
+    /// `set_operation_enabled("transfer", ..)`. Only the contract owner can call this.
+    pub fn set_transfers_enabled(&mut self, transfers_enabled: bool) {
+        self.set_operation_enabled(Operation::Transfer, transfers_enabled);
+    }
+
+    /// Whether holders can currently transfer, swap and transfer-call their badges
+    pub fn get_transfers_enabled(&self) -> bool {
+        self.is_operation_enabled(Operation::Transfer)
+    }
+
+    /// Send mint fees and other protocol charges to `account_id` from now on and log a `treasury_update` event.
+    /// Only the contract owner can call this.
+    pub fn set_treasury(&mut self, account_id: AccountId) {
+        self.assert_contract_owner();
+        let old_treasury_id = std::mem::replace(&mut self.treasury_id, account_id);
+
+        EventLogVariant::TreasuryUpdate(vec![TreasuryUpdateLog {
+            old_treasury_id: old_treasury_id.to_string(),
+            new_treasury_id: self.treasury_id.to_string(),
+        }]).emit();
+    }
+
+    /// The account mint fees and other protocol charges are sent to
+    pub fn get_treasury(&self) -> AccountId {
+        self.treasury_id.clone()
+    }
+
+    /// Charge a flat fee in yoctoNEAR for every minted token on top of its price and send it to the treasury.
+    /// 0 turns the fee off. Only the contract owner can call this.
+    pub fn set_mint_fee(&mut self, mint_fee: U128) {
+        self.assert_contract_owner();
+        self.mint_fee = mint_fee.0;
+    }
+
+    /// The fee charged for every minted token, in yoctoNEAR
+    pub fn get_mint_fee(&self) -> U128 {
+        U128(self.mint_fee)
+    }
+
+    /// Cap the total royalty any series can set, in basis points (1000 = 10%). Series created or updated afterwards
+    /// can't exceed it, existing royalties are left as they are. 10000 removes the cap. Only the contract owner can call this.
+    pub fn set_max_royalty_bps(&mut self, max_royalty_bps: u32) {
+        self.assert_contract_owner();
+        require!(max_royalty_bps <= 10_000, "The royalty cap can't be more than 10000");
+        self.max_royalty_bps = max_royalty_bps;
+    }
+
+    /// The most royalty any series can set, in basis points
+    pub fn get_max_royalty_bps(&self) -> u32 {
+        self.max_royalty_bps
+    }
+
+    /// Require non-zero series prices to be at least `min_series_price` yoctoNEAR. Free series are always allowed.
+    /// Series created or repriced afterwards must follow it, existing prices are left as they are. 0 removes the
+    /// minimum. Only the contract owner can call this.
+    pub fn set_min_series_price(&mut self, min_series_price: U128) {
+        self.assert_contract_owner();
+        self.min_series_price = min_series_price.0;
+    }
+
+    /// The lowest non-zero price a series can set, in yoctoNEAR
+    pub fn get_min_series_price(&self) -> U128 {
+        U128(self.min_series_price)
+    }
+
+    /// Repair the per-owner token index from `tokens_by_id`, which is authoritative, in case a bug ever desyncs them.
+    /// For the `limit` tokens starting at `from_index` (in `nft_tokens` order), the token is added back to its
+    /// owner's set and any token in that set the owner doesn't actually hold is dropped. The owner's series holdings
+    /// are then recounted from the repaired set. Stale entries of accounts that own none of the scanned tokens aren't
+    /// visited. Returns how many entries were fixed. Only the contract owner can call this.
+    pub fn reconcile_owner_index(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_contract_owner();
+
+        //group the scanned tokens by owner so each owner's set is checked once per call
+        let token_ids = self.tokens_by_id.keys_as_vector();
+        let start = from_index.min(token_ids.len());
+        let end = start.saturating_add(limit).min(token_ids.len());
+        let mut scanned_by_owner: BTreeMap<AccountId, Vec<TokenId>> = BTreeMap::new();
+        for token_id in (start..end).filter_map(|index| token_ids.get(index)) {
+            let owner_id = self.tokens_by_id.get(&token_id).unwrap().owner_id;
+            scanned_by_owner.entry(owner_id).or_default().push(token_id);
+        }
+
+        let mut fixed = 0;
+        for (owner_id, scanned) in scanned_by_owner {
+            let mut tokens_set = self.tokens_per_owner.get(&owner_id).unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::TokenPerOwnerInner {
+                    account_id_hash: hash_account_id(&owner_id.to_string()),
+                })
+            });
+
+            for token_id in scanned {
+                if tokens_set.insert(&token_id) {
+                    fixed += 1;
+                }
+            }
+            let stale: Vec<TokenId> = tokens_set
+                .iter()
+                .filter(|held| self.tokens_by_id.get(held).is_none_or(|token| token.owner_id != owner_id))
+                .collect();
+            //the owner may have stopped holding the series of the dropped tokens, so those are recounted too
+            let mut dropped_series = HashSet::new();
+            for held in stale {
+                tokens_set.remove(&held);
+                dropped_series.extend(self.tokens_by_id.get(&held).map(|token| token.series_id));
+                fixed += 1;
+            }
+            self.tokens_per_owner.insert(&owner_id, &tokens_set);
+
+            fixed += self.internal_recount_series_holdings(&owner_id, dropped_series);
+        }
+        fixed
+    }
+
+    /// Permanently freeze the contract metadata so `update_metadata` always fails afterwards.
+    /// There's no way to unlock it. Only the contract owner can call this.
+    pub fn lock_contract_metadata(&mut self) {
+        self.assert_contract_owner();
+        self.metadata_locked = true;
+    }
+
+    /// Move a token between accounts, bypassing the transfer allowlist that keeps badges soulbound.
+    /// Meant for rotating DevHub's own service accounts. Only the contract owner can call this and
+    /// the `reason` is recorded in an `admin_transfer` event next to the standard `nft_transfer` one.
+    pub fn admin_transfer(&mut self, token_id: TokenId, new_owner_id: AccountId, reason: String) {
+        self.assert_contract_owner();
+
+        let token = self.internal_get_token(&token_id);
+        require!(
+            token.owner_id != new_owner_id,
+            "The token owner and the receiver should be different"
+        );
+        self.internal_reassign_token(&token, &token_id, &new_owner_id);
+
+        //the previous owner gets back the storage they paid for the approvals that were reset
+        refund_approved_account_ids(token.owner_id.clone(), &token.approved_account_ids);
+
+        let authorized_id = env::predecessor_account_id().to_string();
+        EventLogVariant::NftTransfer(vec![NftTransferLog {
+            authorized_id: Some(authorized_id.clone()),
+            old_owner_id: token.owner_id.to_string(),
+            new_owner_id: new_owner_id.to_string(),
+            token_ids: vec![token_id.clone()],
+            memo: None,
+        }]).emit();
+
+        EventLogVariant::AdminTransfer(vec![AdminTransferLog {
+            authorized_id,
+            old_owner_id: token.owner_id.to_string(),
+            new_owner_id: new_owner_id.to_string(),
+            token_ids: vec![token_id],
+            reason,
+        }]).emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::{accounts, get_logs};
+    use crate::nft_core::NonFungibleTokenCore;
+    use crate::metadata::NonFungibleTokenMetadata;
+
+    #[test]
+    fn get_owner_returns_the_account_the_contract_was_created_with() {
+        let contract = setup_contract();
+
+        assert_eq!(contract.get_owner(), owner());
+    }
+
+    #[test]
+    fn owner_and_approved_creators_can_create_series() {
+        let mut contract = setup_contract();
+        contract.add_approved_creator(accounts(2));
+        contract.remove_approved_creator(owner());
+
+        assert!(contract.can_create_series(owner()));
+        assert!(contract.can_create_series(accounts(2)));
+        assert!(!contract.can_create_series(accounts(3)));
+
+        //the view matches what create_series allows
+        create_sample_series(&mut contract, 1);
+        set_context(accounts(3), STORAGE_DEPOSIT);
+        let create = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create_series_from_spec(series_spec(2, sample_metadata("Badge")), None);
+        }));
+        assert!(create.unwrap_err().downcast_ref::<String>().unwrap().contains("only approved creators can add a type"));
+    }
+
+    #[test]
+    fn approved_lists_reflect_grants_and_revocations() {
+        let mut contract = setup_contract();
+
+        contract.add_approved_minter(accounts(1));
+        contract.add_approved_creator(accounts(2));
+        assert_eq!(contract.get_approved_minters(), vec![owner(), accounts(1)]);
+        assert_eq!(contract.get_approved_creators(), vec![owner(), accounts(2)]);
+
+        contract.remove_approved_minter(owner());
+        contract.remove_approved_creator(accounts(2));
+        assert_eq!(contract.get_approved_minters(), vec![accounts(1)]);
+        assert_eq!(contract.get_approved_creators(), vec![owner()]);
+    }
+
+    #[test]
+    fn repeated_grants_report_no_change() {
+        let mut contract = setup_contract();
+
+        assert!(contract.add_approved_minter(accounts(1)));
+        assert!(!contract.add_approved_minter(accounts(1)));
+        assert!(!contract.add_approved_creator(owner()));
+        assert!(contract.remove_approved_creator(owner()));
+        assert!(!contract.remove_approved_creator(owner()));
+
+        let batch = vec![accounts(2), accounts(3)];
+        assert_eq!(contract.set_allowed_addresses(batch.clone()).added, 2);
+        assert_eq!(contract.set_allowed_addresses(batch), BatchInsertResult { added: 0, already_present: 2 });
+    }
+
+    fn surplus() -> Balance {
+        env::account_balance() - Balance::from(env::storage_usage()) * env::storage_byte_cost()
+    }
+
+    #[test]
+    fn owner_can_withdraw_surplus() {
+        let mut contract = setup_contract();
+        set_context(owner(), 0);
+
+        contract.owner_withdraw_surplus(U128(surplus()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only withdraw up to")]
+    fn withdrawing_more_than_surplus_is_rejected() {
+        let mut contract = setup_contract();
+        set_context(owner(), 0);
+
+        contract.owner_withdraw_surplus(U128(surplus() + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only withdraw up to")]
+    fn storage_deposits_are_not_surplus() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.deposit_storage(None);
+        assert!(contract.storage_deposit_of(accounts(1)).0 > 0);
+
+        set_context(owner(), 0);
+        contract.owner_withdraw_surplus(U128(surplus() - contract.storage_deposits_total + 1));
+    }
+
+    #[test]
+    fn surplus_beyond_storage_deposits_can_be_withdrawn() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.deposit_storage(None);
+
+        set_context(owner(), 0);
+        assert_eq!(contract.storage_deposits_total, contract.storage_deposit_of(accounts(1)).0);
+        contract.owner_withdraw_surplus(U128(surplus() - contract.storage_deposits_total));
+    }
+
+    #[test]
+    #[should_panic(expected = "only contract owner")]
+    fn only_owner_can_withdraw_surplus() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), 0);
+
+        contract.owner_withdraw_surplus(U128(1));
+    }
+
+    #[test]
+    fn admin_transfer_moves_a_token_outside_the_allowlist() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(owner(), 0);
+        contract.admin_transfer(token_id.clone(), accounts(2), "rotate service account".to_string());
+
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(2));
+        let logs = get_logs();
+        assert!(logs[0].contains(r#""event":"nft_transfer""#));
+        assert!(logs[1].contains(r#""event":"admin_transfer""#));
+        assert!(logs[1].contains(r#""reason":"rotate service account""#));
+    }
+
+    #[test]
+    #[should_panic(expected = "only contract owner")]
+    fn admin_transfer_requires_the_contract_owner() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), 0);
+        contract.admin_transfer(token_id, accounts(2), "rotate service account".to_string());
+    }
+
+    #[test]
+    fn disabling_transfers_blocks_transfers_but_not_mints() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_transferable(1, true);
+        contract.set_allowed_addresses(vec![accounts(2)]);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(owner(), 0);
+        contract.set_transfers_enabled(false);
+        let new_token_id = mint_to(&mut contract, 1, accounts(1));
+        set_context(accounts(1), 1);
+        let transfer = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_transfer(accounts(2), token_id.clone(), None, None);
+        }));
+
+        assert!(transfer.unwrap_err().downcast_ref::<String>().unwrap().contains("Transfer is disabled"));
+        assert!(!contract.get_transfers_enabled());
+        assert!(!contract.is_operation_enabled(Operation::Transfer));
+        assert_eq!(contract.nft_token(new_token_id).unwrap().owner_id, accounts(1));
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+    }
+
+    #[test]
+    fn locking_contract_metadata_blocks_updates() {
+        let mut contract = setup_contract();
+        let mut metadata = contract.nft_metadata();
+        metadata.name = "Renamed".to_string();
+        set_context(owner(), 0);
+        contract.update_metadata(owner(), metadata.clone());
+
+        set_context(owner(), 0);
+        contract.lock_contract_metadata();
+        let update = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_metadata(owner(), contract.nft_metadata());
+        }));
+
+        assert!(update.unwrap_err().downcast_ref::<String>().unwrap().contains("Contract metadata is locked"));
+        assert_eq!(contract.nft_metadata().name, "Renamed");
+    }
+
+    #[test]
+    fn updating_contract_metadata_logs_the_new_metadata() {
+        let mut contract = setup_contract();
+        let mut metadata = contract.nft_metadata();
+        metadata.name = "Renamed".to_string();
+        set_context(owner(), 0);
+        contract.update_metadata(owner(), metadata);
+
+        let log = near_sdk::test_utils::get_logs().pop().unwrap();
+        assert!(log.contains(r#""event":"contract_metadata_update""#));
+        assert!(log.contains(r#""metadata":{"spec":"nft-1.0.0","name":"Renamed""#));
+    }
+
+    #[test]
+    fn storage_stats_grow_with_mints() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let before = contract.storage_stats();
+
+        mint_to(&mut contract, 1, accounts(1));
+        let after = contract.storage_stats();
+
+        assert!(before.storage_usage > 0);
+        assert!(after.storage_usage > before.storage_usage);
+        assert_eq!(after.storage_cost_yocto.0, Balance::from(after.storage_usage) * env::storage_byte_cost());
+    }
+
+    #[test]
+    fn mint_fees_go_to_the_treasury() {
+        let mut contract = setup_contract();
+        let fee = STORAGE_DEPOSIT / 100;
+        contract.set_mint_fee(U128(fee));
+        contract.set_treasury(accounts(4));
+        let log = get_logs().pop().unwrap();
+        assert!(log.starts_with(r#"EVENT_JSON:{"standard":"devhub_badges","version":"1.0.0","event":"treasury_update""#));
+        create_sample_series(&mut contract, 1);
+
+        mint_to(&mut contract, 1, accounts(1));
+
+        assert_eq!(transfers_to(&accounts(4)), vec![fee]);
+        assert_eq!(contract.get_treasury(), accounts(4));
+    }
+
+    #[test]
+    fn reconciling_restores_a_corrupted_owner_index() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let first = mint_to(&mut contract, 1, accounts(1));
+        let second = mint_to(&mut contract, 1, accounts(1));
+        let other = mint_to(&mut contract, 1, accounts(2));
+
+        //lose one of accounts(1)'s tokens and list it under accounts(2) as well
+        let mut tokens = contract.tokens_per_owner.get(&accounts(1)).unwrap();
+        tokens.remove(&first);
+        contract.tokens_per_owner.insert(&accounts(1), &tokens);
+        let mut tokens = contract.tokens_per_owner.get(&accounts(2)).unwrap();
+        tokens.insert(&first);
+        contract.tokens_per_owner.insert(&accounts(2), &tokens);
+
+        set_context(owner(), 0);
+        assert_eq!(contract.reconcile_owner_index(0, 2), 1);
+        assert_eq!(contract.reconcile_owner_index(2, 2), 1);
+        assert_eq!(contract.reconcile_owner_index(0, 10), 0);
+
+        let held = |contract: &Contract, account_id: AccountId| -> Vec<TokenId> {
+            let mut held = contract.tokens_per_owner.get(&account_id).unwrap().to_vec();
+            held.sort();
+            held
+        };
+        assert_eq!(held(&contract, accounts(1)), vec![first, second]);
+        assert_eq!(held(&contract, accounts(2)), vec![other]);
+    }
+
+    #[test]
+    fn reconciling_recounts_the_series_holdings() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        mint_to(&mut contract, 1, accounts(1));
+        let moved = mint_to(&mut contract, 2, accounts(1));
+        mint_to(&mut contract, 1, accounts(2));
+        //accounts(2) is counted as holding 3 tokens of series 1, and the series 2 token moved to accounts(2) without
+        //its index or counts following
+        contract.series_holdings.insert(&(1, accounts(2)), &3);
+        let mut token = contract.tokens_by_id.get(&moved).unwrap();
+        token.owner_id = accounts(2);
+        contract.tokens_by_id.insert(&moved, &token);
+
+        set_context(owner(), 0);
+        //accounts(2)'s set gains the moved token and both its counts change, accounts(1) loses the token and the count
+        assert_eq!(contract.reconcile_owner_index(0, 10), 5);
+        assert_eq!(contract.reconcile_owner_index(0, 10), 0);
+
+        assert_eq!(contract.series_holdings.get(&(1, accounts(2))), Some(1));
+        assert_eq!(contract.holders_check(2, vec![accounts(1), accounts(2)]), vec![false, true]);
+        assert_eq!(contract.series_unique_owners(2), 1);
+    }
 }
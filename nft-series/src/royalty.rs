@@ -1,7 +1,9 @@
 use crate::*;
 
-pub trait NonFungibleTokenCore {
+pub trait NonFungibleTokenRoyalty {
     //calculates the payout for a token given the passed in balance. This is a view method
+    //every share, the seller's included, is `floor(balance * basis points / 10000)` and the dust left over is paid to
+    //the series owner, so the payout always sums to the balance to the yoctoNEAR. There is no other rounding mode
     fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32) -> Payout;
 
     //transfers the token to the receiver ID and returns the payout object that should be payed given the passed in balance.
@@ -17,68 +19,22 @@ pub trait NonFungibleTokenCore {
 }
 
 #[near_bindgen]
-impl NonFungibleTokenCore for Contract {
+impl NonFungibleTokenRoyalty for Contract {
     //calculates the payout for a token given the passed in balance. This is a view method
     fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32) -> Payout {
         //get the token object
-        let token = self.tokens_by_id.get(&token_id).expect("No token");
-
-        //get the owner of the token
-        let owner_id = token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //get the u128 version of the passed in balance (which was U128 before)
-        let balance_u128 = u128::from(balance);
-        //keep track of the payout object to send back
-        let mut payout_object = Payout {
-            payout: HashMap::new(),
-        };
-        //get the royalty object from series
-        let cur_series = self
-            .series_by_id
-            .get(&token.series_id)
-            .expect("Not a series");
-
-        // If the series doesn't have a royalty, we'll return an a payout object that just includes the owner
-        let royalty_option = cur_series.royalty;
-        if royalty_option.is_none() {
-            let mut payout = HashMap::new();
-            payout.insert(owner_id, balance);
-            return Payout {
-                payout: payout
-            };
-        }
-        // Otherwise, we will get the royalty object from the series
-        let royalty = royalty_option.unwrap();
-
-        //make sure we're not paying out to too many people (GAS limits this)
-        assert!(
-            royalty.len() as u32 <= max_len_payout,
-            "Market can&not payout to that many receivers"
-        );
+        let token = self.internal_get_token(&token_id);
 
-        //go through each key and value in the royalty object
-        for (k, v) in royalty.iter() {
-            //get the key
-            let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-            if key != owner_id {
-                //
-                payout_object
-                    .payout
-                    .insert(key, royalty_to_payout(*v, balance_u128));
-                total_perpetual += *v;
-            }
-        }
-
-        // payout to previous owner who gets 100% - total perpetual royalties
-        payout_object.payout.insert(
-            owner_id,
-            royalty_to_payout(10000 - total_perpetual, balance_u128),
-        );
+        //get the royalty object from series
+        let cur_series = self.internal_get_series(token.series_id);
 
-        //return the payout object
-        payout_object
+        royalty_payout(
+            cur_series.royalty.as_ref(),
+            token.owner_id,
+            cur_series.owner_id,
+            balance.into(),
+            max_len_payout,
+        )
     }
 
     //transfers the token to the receiver ID and returns the payout object that should be payed given the passed in balance.
@@ -106,62 +62,222 @@ impl NonFungibleTokenCore for Contract {
             &previous_token.approved_account_ids,
         );
 
-        //get the owner of the token
-        let owner_id = previous_token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //get the u128 version of the passed in balance (which was U128 before)
-        let balance_u128 = u128::from(balance);
-        //keep track of the payout object to send back
-        let mut payout_object = Payout {
-            payout: HashMap::new(),
-        };
-
         //get the royalty object from series
-        let cur_series = self
-            .series_by_id
-            .get(&previous_token.series_id)
-            .expect("Not a series");
-
-        // If the series doesn't have a royalty, we'll return an a payout object that just includes the owner
-        let royalty_option = cur_series.royalty;
-        if royalty_option.is_none() {
-            let mut payout = HashMap::new();
-            payout.insert(owner_id, balance);
-            return Payout {
-                payout: payout
-            };
-        }
-        // Otherwise, we will get the royalty object from the series
-        let royalty = royalty_option.unwrap();
+        let cur_series = self.internal_get_series(previous_token.series_id);
 
-        //make sure we're not paying out to too many people (GAS limits this)
-        assert!(
-            royalty.len() as u32 <= max_len_payout,
-            "Market cannot payout to that many receivers"
-        );
+        royalty_payout(
+            cur_series.royalty.as_ref(),
+            previous_token.owner_id,
+            cur_series.owner_id,
+            balance.into(),
+            max_len_payout,
+        )
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Whether a payout for the token fits in `max_len_payout` receivers: its series' royalty recipients, the
+    /// token owner and, if the series has a royalty, the series owner who is paid the rounding dust. Marketplaces
+    /// can check this before listing instead of having `nft_transfer_payout` fail.
+    pub fn is_payout_payable(&self, token_id: TokenId, max_len_payout: u32) -> bool {
+        let token = self.internal_get_token(&token_id);
+        let series = self.internal_get_series(token.series_id);
+
+        payout_receiver_count(series.royalty.as_ref(), &token.owner_id, &series.owner_id) <= max_len_payout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn malformed_royalty_recipient_is_rejected() {
+        //this is how the royalty argument of create_series and update_series_royalty is parsed
+        let royalty = near_sdk::serde_json::json!({ "Not A Valid Account": 1_000 });
+
+        assert!(near_sdk::serde_json::from_value::<Option<HashMap<AccountId, u32>>>(royalty).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty shares can't add up to more than 10000")]
+    fn royalty_shares_above_100_percent_are_rejected() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 6_000), (accounts(4), 5_000)]);
+
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty shares must add up to more than 0")]
+    fn all_zero_royalty_shares_are_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        contract.update_series_royalty(1, Some(HashMap::from([(accounts(3), 0), (accounts(4), 0)])));
+    }
+
+    #[test]
+    fn rounding_dust_is_paid_to_the_series_owner() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 3_333)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        let payout = contract.nft_payout(token_id, U128(10), 10).payout;
+
+        assert_eq!(payout[&accounts(3)], U128(3));
+        assert_eq!(payout[&accounts(1)], U128(6));
+        assert_eq!(payout[&owner()], U128(1));
+        assert_eq!(payout.values().map(|amount| amount.0).sum::<Balance>(), 10);
+    }
 
-        //go through each key and value in the royalty object
-        for (k, v) in royalty.iter() {
-            //get the key
-            let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-            if key != owner_id {
-                //
-                payout_object
-                    .payout
-                    .insert(key, royalty_to_payout(*v, balance_u128));
-                total_perpetual += *v;
-            }
+    #[test]
+    fn payouts_across_three_recipients_sum_to_the_balance() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 3_333), (accounts(4), 1_111), (accounts(5), 777)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        for balance in [1, 7, 9_999, 10_001, 123_456_789, 10u128.pow(24) + 1, u128::MAX] {
+            let payout = contract.nft_payout(token_id.clone(), U128(balance), 10).payout;
+
+            assert_eq!(payout[&accounts(3)].0, floor_share(balance, 3_333));
+            assert_eq!(payout[&accounts(4)].0, floor_share(balance, 1_111));
+            assert_eq!(payout[&accounts(5)].0, floor_share(balance, 777));
+            assert_eq!(payout[&accounts(1)].0, floor_share(balance, 10_000 - 3_333 - 1_111 - 777));
+            assert_eq!(payout.values().map(|amount| amount.0).fold(0u128, |sum, amount| sum.checked_add(amount).unwrap()), balance);
         }
+    }
 
-        // payout to previous owner who gets 100% - total perpetual royalties
-        payout_object.payout.insert(
-            owner_id,
-            royalty_to_payout(10000 - total_perpetual, balance_u128),
-        );
+    //the rounding rule spelled out with arbitrary precision, for balances whose product would overflow a u128
+    fn floor_share(balance: Balance, basis_points: u128) -> Balance {
+        let (high, low) = (balance >> 64, balance & u128::from(u64::MAX));
+        let (high_part, low_part) = (high * basis_points, low * basis_points);
+        //(high * 2^64 + low) * bps / 10000, divided one 64-bit half at a time so nothing overflows
+        let quotient_high = high_part / 10_000;
+        let remainder = high_part % 10_000;
+        let low_total = (remainder << 64) + low_part;
+        (quotient_high << 64) + low_total / 10_000
+    }
+
+    #[test]
+    #[should_panic(expected = "Market cannot payout to that many receivers")]
+    fn payout_counts_the_series_owner_who_may_get_the_dust() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 3_333)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        //the recipient and the token owner would fit, but the dust goes to the series owner as a third receiver
+        contract.nft_payout(token_id, U128(10), 2);
+    }
+
+    #[test]
+    fn payout_is_payable_when_every_receiver_fits_the_limit() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 500), (accounts(4), 500)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        //two recipients, the token owner and the series owner, who may get the rounding dust
+        assert!(contract.is_payout_payable(token_id.clone(), 4));
+        assert!(!contract.is_payout_payable(token_id, 3));
+    }
+
+    #[test]
+    fn payable_payout_never_has_more_receivers_than_the_limit() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 3_333)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        //the series owner holding a token is paid once, as its owner
+        let owned_token_id = mint_to(&mut contract, 1, owner());
+
+        assert!(!contract.is_payout_payable(token_id.clone(), 2));
+        assert!(contract.is_payout_payable(token_id.clone(), 3));
+        assert_eq!(contract.nft_payout(token_id, U128(10), 3).payout.len(), 3);
+        assert!(contract.is_payout_payable(owned_token_id.clone(), 2));
+        assert_eq!(contract.nft_payout(owned_token_id, U128(10), 2).payout.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Market cannot payout to that many receivers")]
+    fn payout_fails_when_it_isnt_payable() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 500), (accounts(4), 500)]);
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        contract.nft_payout(token_id, U128(10), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty recipient system can't receive NEAR")]
+    fn royalty_to_an_account_that_cant_receive_near_is_rejected() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        contract.update_series_royalty(1, Some(HashMap::from([("system".parse().unwrap(), 500)])));
+    }
+
+    #[test]
+    fn royalty_to_a_well_formed_account_pays_out() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.update_series_royalty(1, Some(HashMap::from([(accounts(3), 500)])));
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        let payout = contract.nft_payout(token_id, U128(10_000), 10).payout;
+
+        assert_eq!(payout[&accounts(3)], U128(500));
+        assert_eq!(payout[&accounts(1)], U128(9_500));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty shares can't add up to more than the contract's cap of 1000")]
+    fn royalty_above_the_contract_cap_is_rejected() {
+        let mut contract = setup_contract();
+        contract.set_max_royalty_bps(1_000);
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 600), (accounts(4), 401)]);
+
+        contract.create_series_from_spec(SeriesSpec { royalty: Some(royalty), ..series_spec(1, sample_metadata("Badge")) }, None);
+    }
+
+    #[test]
+    fn royalty_at_the_contract_cap_is_accepted() {
+        let mut contract = setup_contract();
+        contract.set_max_royalty_bps(1_000);
+        create_sample_series(&mut contract, 1);
+
+        contract.update_series_royalty(1, Some(HashMap::from([(accounts(3), 600), (accounts(4), 400)])));
+        contract.update_series_royalty(1, Some(HashMap::from([(accounts(3), 250)])));
+
+        assert_eq!(contract.get_max_royalty_bps(), 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Stored royalty shares add up to 11000 basis points, more than 10000")]
+    fn payout_rejects_a_stored_royalty_over_100_percent() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        //write the map straight to state, as a series created before royalties were validated could hold it
+        let mut series = contract.series_by_id.get(&1).unwrap();
+        series.royalty = Some(HashMap::from([(accounts(3), 6_000), (accounts(4), 5_000)]));
+        contract.series_by_id.insert(&1, &series);
 
-        //return the payout object
-        payout_object
+        contract.nft_payout(token_id, U128(10_000), 10);
     }
 }
@@ -1,153 +1,1198 @@
+// `create_series` keeps its flat JSON arguments, and the wrappers `near_bindgen` generates for it can't carry a
+// function-level allow
+#![allow(clippy::too_many_arguments)]
+
 use near_sdk::json_types::U64;
+use std::collections::HashSet;
 
 use crate::*;
 
+/// The most tokens `nft_batch_mint` mints in one call
+pub const MAX_BATCH_MINT: usize = 100;
+/// The most series `batch_create_series` creates in one call
+pub const MAX_BATCH_CREATE_SERIES: usize = 50;
+
+/// One series to create with `create_series_from_spec` or `batch_create_series`. The fields mean the same as the
+/// `create_series` arguments. The minting rules can be left out and default to those of a new series (see the
+/// `set_series_*` methods).
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesSpec {
+    pub id: SeriesId,
+    pub metadata: TokenMetadata,
+    pub royalty: Option<HashMap<AccountId, u32>>,
+    pub price: Option<U128>,
+    pub description: Option<String>,
+    pub external_url: Option<String>,
+    pub visible: Option<bool>,
+    pub collection_media: Option<String>,
+    pub transferable: Option<bool>,
+    pub unique_per_account: Option<bool>,
+    pub claim_only: Option<bool>,
+    pub public_mint_enabled: Option<bool>,
+    pub claim_deadline: Option<u64>,
+    pub on_mint_callback: Option<AccountId>,
+    pub storage_payer: Option<StoragePayer>,
+}
+
 #[near_bindgen]
 impl Contract {
     /// Create a new series. The caller must be an approved creator. All tokens in the series will inherit the same metadata
     /// If copies are set in the metadata, it will enforce that only that number of NFTs can be minted. If not, unlimited NFTs can be minted.
     /// If a title is set in the metadata, enumeration methods will return the `${title} - ${edition}` else, `${series_id} - ${edition}`
     /// All token IDs internally are stored as `${series_id}:${edition}`
+    /// Series are visible in public listings unless `visible` is set to false.
     /// Caller must attach enough $NEAR to cover storage.
+    /// If an `external_id` is passed and the caller already created a series with it, the call is a no-op
+    /// that refunds the deposit and returns the existing series ID. This makes retried creations safe.
+    /// To set the minting rules at creation too, use `create_series_from_spec`.
     #[payable]
     pub fn create_series(
         &mut self,
         id: u64,
         metadata: TokenMetadata,
         royalty: Option<HashMap<AccountId, u32>>,
-        price: Option<U128>
-    ) {
+        price: Option<U128>,
+        external_id: Option<String>,
+        description: Option<String>,
+        external_url: Option<String>,
+        visible: Option<bool>,
+        collection_media: Option<String>,
+    ) -> SeriesId {
+        let spec = SeriesSpec {
+            id,
+            metadata,
+            royalty,
+            price,
+            description,
+            external_url,
+            visible,
+            collection_media,
+            transferable: None,
+            unique_per_account: None,
+            claim_only: None,
+            public_mint_enabled: None,
+            claim_deadline: None,
+            on_mint_callback: None,
+            storage_payer: None,
+        };
+        self.create_series_from_spec(spec, external_id)
+    }
+
+    /// Create a new series from `spec`, like `create_series` but with every option `batch_create_series` accepts.
+    /// Minting rules left out of the spec default to those of a new series (see the `set_series_*` methods).
+    /// The same `external_id` retry rule applies and the caller attaches enough $NEAR to cover storage.
+    #[payable]
+    pub fn create_series_from_spec(&mut self, spec: SeriesSpec, external_id: Option<String>) -> SeriesId {
         // Measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
         // Ensure the caller is an approved creator
         let caller = env::predecessor_account_id();
         require!(
-            self.approved_creators.contains(&caller) == true,
-            "only approved creators can add a type"
+            self.internal_can_create_series(&caller),
+            ContractError::Unauthorized.message("only approved creators can add a type")
         );
 
+        assert_valid_royalty(&spec.royalty, self.max_royalty_bps);
+        assert_valid_series_metadata(&spec.metadata);
+        assert_max_length("Description", &spec.description, MAX_DESCRIPTION_LENGTH);
+
+        // External IDs are scoped to the creator so one creator can't block another's retries
+        let external_key = external_id.map(|external_id| format!("{}:{}", caller, external_id));
+        if let Some(existing_id) = external_key
+            .as_ref()
+            .and_then(|key| self.series_by_external_id.get(key))
+        {
+            // Nothing was written, so the whole deposit is refunded
+            refund_deposit(0);
+            return existing_id;
+        }
+
         // Insert the series and ensure it doesn't already exist
-        require!(
-            self.series_by_id
-                .insert(
-                    &id,
-                    &Series {
-                        metadata,
-                        royalty,
-                        tokens: UnorderedSet::new(StorageKey::SeriesByIdInner {
-                            // We get a new unique prefix for the collection
-                            account_id_hash: hash_account_id(&format!(
-                                "{}{}",
-                                id, caller
-                            )),
-                        }),
-                        owner_id: caller,
-                        price: price.map(|p| p.into()),
-                    }
-                )
-                .is_none(),
-            "collection ID already exists"
-        );
+        let id = self.internal_insert_series(caller, spec);
+
+        // Remember which series this external ID created
+        if let Some(key) = external_key {
+            self.series_by_external_id.insert(&key, &id);
+        }
 
         //calculate the required storage which was the used - initial
         let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
 
         //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
         refund_deposit(required_storage_in_bytes);
+
+        id
     }
 
-    /// Mint a new NFT that is part of a series. The caller must be an approved minter.
-    /// The series ID must exist and if the metadata specifies a copy limit, you cannot exceed it.
+    /// Create a new series with the same configuration as `source_series_id`, i.e. everything `export_series_spec`
+    /// exports: metadata, royalty, price, listing details and minting rules (including public minting, the claim
+    /// deadline, the storage payer and the mint callback). Tokens, revenue, eligible receivers, locks and pauses aren't
+    /// copied and the caller owns the new series. Hidden series can only be cloned by their owner. The caller must be
+    /// an approved creator and attach enough $NEAR to cover storage.
     #[payable]
-    pub fn nft_mint(&mut self, id: U64, receiver_id: AccountId) {
-         // Check if the receiver is in the allowed transfers list
-        //assert!(self.allowed_transfers.contains(&receiver_id), "Transfer not allowed to this address");
+    pub fn clone_series(&mut self, source_series_id: U64, new_series_id: U64) -> SeriesId {
+        let initial_storage_usage = env::storage_usage();
 
-        // Measure the initial storage being used on the contract
+        let caller = env::predecessor_account_id();
+        require!(
+            self.internal_can_create_series(&caller),
+            ContractError::Unauthorized.message("only approved creators can add a type")
+        );
+
+        let source = self.internal_get_series(source_series_id.0);
+        if !source.visible && source.owner_id != caller {
+            ContractError::SeriesNotFound.panic("Not a series");
+        }
+        let spec = SeriesSpec { id: new_series_id.0, ..self.internal_export_series_spec(source_series_id.0) };
+        let id = self.internal_insert_series(caller, spec);
+
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        id
+    }
+
+    /// Export the series' configuration (metadata and copy limit, royalty, price, listing details and minting rules)
+    /// as a spec that `batch_create_series` accepts, e.g. to back a program up or replicate it on another deployment.
+    /// Tokens, revenue, the owner, eligible receivers, claim allowances, locks and pauses aren't part of it.
+    /// Like `get_series_details`, hidden series can't be exported until they're made visible.
+    pub fn export_series_spec(&self, series_id: SeriesId) -> SeriesSpec {
+        if !self.internal_get_series(series_id).visible {
+            ContractError::SeriesNotFound.panic("Not a series");
+        }
+        self.internal_export_series_spec(series_id)
+    }
+
+    /// Create every series in `specs` in one call, e.g. to bootstrap a DAO's badge programs. The whole batch is
+    /// validated before anything is written: IDs must be unique and unused, and royalties and metadata valid.
+    /// Same rules as `create_series` otherwise, except there are no external IDs. The caller must be an approved
+    /// creator and attach enough $NEAR to cover the storage of all the series. Returns the created series IDs.
+    #[payable]
+    pub fn batch_create_series(&mut self, specs: Vec<SeriesSpec>) -> Vec<SeriesId> {
         let initial_storage_usage = env::storage_usage();
 
-        // Get the series and how many tokens currently exist (edition number = cur_len + 1)
-        let mut series = self.series_by_id.get(&id.0).expect("Not a series");
-        
-        // Check if the series has a price per token. If it does, ensure the caller has attached at least that amount
-        let mut price_per_token = 0; 
-        if let Some(price) = series.price {
-            price_per_token = price;
-            require!(env::attached_deposit() > price_per_token, "Need to attach at least enough to cover price");
-        // If the series doesn't have a price, ensure the caller is an approved minter.
-        } else {
-            // Ensure the caller is an approved minter
-            let predecessor = env::predecessor_account_id();
-            assert!(
-                self.approved_minters.contains(&predecessor),
-                "Not approved minter"
+        let caller = env::predecessor_account_id();
+        require!(
+            self.internal_can_create_series(&caller),
+            ContractError::Unauthorized.message("only approved creators can add a type")
+        );
+        require!(!specs.is_empty(), "Nothing to create");
+        require!(
+            specs.len() <= MAX_BATCH_CREATE_SERIES,
+            format!("Can create at most {} series at once", MAX_BATCH_CREATE_SERIES)
+        );
+
+        let mut ids = HashSet::new();
+        for spec in &specs {
+            require!(ids.insert(spec.id), format!("Series ID {} is in the batch twice", spec.id));
+            require!(
+                self.series_by_id.get(&spec.id).is_none(),
+                format!("collection ID {} already exists", spec.id)
             );
+            assert_valid_royalty(&spec.royalty, self.max_royalty_bps);
+            assert_valid_series_metadata(&spec.metadata);
+            assert_max_length("Description", &spec.description, MAX_DESCRIPTION_LENGTH);
+        }
+
+        let created = specs
+            .into_iter()
+            .map(|spec| self.internal_insert_series(caller.clone(), spec))
+            .collect();
+
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        created
+    }
+
+    /// Mint a new NFT that is part of a series. The caller must be an approved minter.
+    /// Other contracts can mint too (see `ext_badges`): the calling contract is the one that must be an approved
+    /// minter, whoever signed the transaction, and `receiver_id` is the account that gets the badge.
+    /// The series ID must exist and if the metadata specifies a copy limit, you cannot exceed it.
+    /// Whatever the deposit doesn't need for the price and storage is refunded to `refund_to`, or the caller
+    /// if it's not given (e.g. a relayer passes the account it's minting for).
+    /// Retrying with the same `request_id` returns the token the first call minted instead of minting another
+    /// (and refunds the whole deposit). Request IDs are scoped to the caller.
+    /// An `external_id` (e.g. the issuer's database row ID) makes the token retrievable with `token_by_external_id`.
+    /// External IDs are unique across the contract until the token is burned.
+    /// A `credential` JSON (e.g. issuer DID, skill name and score) is shown under the `credential` key of the token's
+    /// `extra` metadata so verifiers can read it. Returns the minted token ID.
+    #[payable]
+    pub fn nft_mint(
+        &mut self,
+        id: U64,
+        receiver_id: AccountId,
+        refund_to: Option<AccountId>,
+        request_id: Option<String>,
+        external_id: Option<String>,
+        credential: Option<String>,
+    ) -> TokenId {
+        let request_key = request_id.map(|request_id| format!("{}:{}", env::predecessor_account_id(), request_id));
+        if let Some(token_id) = request_key
+            .as_ref()
+            .and_then(|key| self.mints_by_request_id.get(key))
+        {
+            // Nothing was written, so the whole deposit is refunded
+            refund_deposit_to(0, refund_to.unwrap_or_else(env::predecessor_account_id));
+            return token_id;
         }
 
-        let cur_len = series.tokens.len();
-        // Ensure we haven't overflowed on the number of copies minted
-        if let Some(copies) = series.metadata.copies {
+        let options = MintOptions { request_key, external_id, credential };
+        self.internal_mint_many(id.0, vec![(receiver_id, None)], refund_to, false, options).pop().unwrap()
+    }
+
+    /// Mint one token of a series to each of `receiver_ids` in a single call, under the same rules as `nft_mint`.
+    /// The deposit must cover the price, mint fee and, unless someone else pays the series' storage, the worst-case
+    /// storage of every token upfront (see `total_mint_cost`), so an under-funded batch fails before anything is
+    /// written. The unused deposit is refunded to `refund_to`, or the caller if it's not given.
+    #[payable]
+    pub fn nft_batch_mint(
+        &mut self,
+        series_id: U64,
+        receiver_ids: Vec<AccountId>,
+        refund_to: Option<AccountId>,
+    ) -> Vec<TokenId> {
+        require!(!receiver_ids.is_empty(), "Nothing to mint");
+        require!(
+            receiver_ids.len() <= MAX_BATCH_MINT,
+            format!("Can mint at most {} tokens at once", MAX_BATCH_MINT)
+        );
+        let series = self.internal_get_series(series_id.0);
+        let cost_per_token =
+            self.internal_mint_price(&series, false) + self.mint_fee + self.internal_caller_storage_cost(&series);
+        let required_deposit = cost_per_token * receiver_ids.len() as Balance;
+        require!(
+            env::attached_deposit() >= required_deposit,
+            ContractError::Underfunded.message(&format!(
+                "Must attach {} yoctoNEAR to mint {} tokens",
+                required_deposit,
+                receiver_ids.len()
+            ))
+        );
+
+        self.internal_mint_many(
+            series_id.0,
+            receiver_ids.into_iter().map(|receiver_id| (receiver_id, None)).collect(),
+            refund_to,
+            false,
+            MintOptions::default(),
+        )
+    }
+
+    /// Mint a new NFT that is part of a series, personalizing it with per-token metadata.
+    /// Any field set in `overrides` replaces the value derived from the series, the rest fall back to the series.
+    /// The same authorization, price, copy limit and refund rules as `nft_mint` apply.
+    #[payable]
+    pub fn nft_mint_with_overrides(
+        &mut self,
+        series_id: U64,
+        receiver_id: AccountId,
+        overrides: TokenMetadata,
+        refund_to: Option<AccountId>,
+    ) -> TokenId {
+        self.internal_mint(series_id.0, receiver_id, Some(overrides), refund_to)
+    }
+
+    /// Move a token minted under the wrong series into `new_series_id`. The token keeps its ID, takes the next
+    /// edition of the new series and derives its metadata from it from now on. The destination's minting rules apply
+    /// as for a mint to the holder: minting must be enabled and the series not paused, it must have copies left, must
+    /// list the holder as an eligible receiver, and a one-per-account series can't give the holder a second token.
+    /// Only the contract owner, or an account owning both series, can call this.
+    pub fn reassign_token_series(&mut self, token_id: TokenId, new_series_id: U64) {
+        let new_series_id = new_series_id.0;
+        let mut token = self.internal_get_token(&token_id);
+        require!(token.series_id != new_series_id, "The token already belongs to this series");
+        let old_series_id = token.series_id;
+        let mut old_series = self.internal_get_series(old_series_id);
+        let mut new_series = self.internal_get_series(new_series_id);
+
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == self.owner_id || (caller == old_series.owner_id && caller == new_series.owner_id),
+            ContractError::Unauthorized
+                .message("Only the contract owner or the owner of both series can move a token between them")
+        );
+        self.assert_operation_enabled(Operation::Mint);
+        require!(!new_series.minting_paused, ContractError::Paused.message("Series minting paused"));
+        if let Some(copies) = new_series.metadata.copies {
             require!(
-                cur_len < copies,
-                "cannot mint anymore NFTs for the given series. Limit reached"
+                new_series.tokens.len() < copies,
+                ContractError::SoldOut.message("cannot mint anymore NFTs for the given series. Limit reached")
             );
         }
+        require!(
+            new_series.is_eligible_receiver(&token.owner_id),
+            ContractError::Unauthorized.message(&format!("{} isn't eligible to receive this series", token.owner_id))
+        );
+        if new_series.unique_per_account {
+            require!(
+                !self.internal_holds_series_token(&token.owner_id, new_series_id),
+                ContractError::AlreadyHolds.message("Account already holds this badge")
+            );
+        }
+
+        //the token's payment now counts towards the new series' revenue
+        old_series.tokens.remove(&token_id);
+        old_series.revenue = old_series.revenue.saturating_sub(token.paid_amount);
+        new_series.tokens.insert(&token_id);
+        new_series.revenue += token.paid_amount;
+        new_series.editions_minted += 1;
+        token.edition = new_series.editions_minted;
+        token.series_id = new_series_id;
+
+        self.series_by_id.insert(&old_series_id, &old_series);
+        self.series_by_id.insert(&new_series_id, &new_series);
+        self.tokens_by_id.insert(&token_id, &token);
+        self.internal_remove_series_holding(old_series_id, &token.owner_id);
+        self.internal_add_series_holding(new_series_id, &token.owner_id);
+
+        // Emit the event
+        EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+            series_id: None,
+            token_ids: vec![token_id],
+            memo: None,
+        }])
+        .emit();
+    }
+
+    /// Replace a token's per-token metadata in place, e.g. to fix a corrupted badge without burning and reminting it.
+    /// `overrides` replaces the old overrides entirely (`None` goes back to the series metadata). The owner, edition,
+    /// mint time and provenance are kept. Only the series owner can call this and they attach the storage for
+    /// longer overrides (the excess is refunded).
+    #[payable]
+    pub fn replace_token(&mut self, token_id: TokenId, overrides: Option<TokenMetadata>) {
+        let mut token = self.internal_get_token(&token_id);
+        let series = self.internal_get_series(token.series_id);
+        self.assert_series_owner(&series);
+        if let Some(overrides) = &overrides {
+            assert_valid_series_metadata(overrides);
+        }
+        let initial_storage_usage = env::storage_usage();
+
+        token.metadata_overrides = overrides;
+        self.tokens_by_id.insert(&token_id, &token);
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
 
-        // The token ID is stored internally as `${series_id}:${edition}`
-        let token_id = format!("{}:{}", id.0, cur_len + 1);
-        series.tokens.insert(&token_id);
-        self.series_by_id.insert(&id.0, &series);
+        // Emit the event
+        EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+            series_id: None,
+            token_ids: vec![token_id],
+            memo: None,
+        }])
+        .emit();
+    }
+
+    /// The deposit to attach to mint one token of the series: its price, the mint fee and, unless someone else pays
+    /// the series' storage, the storage at the current byte cost. Given the receiver, that's exactly what `nft_mint`
+    /// by `minter_id` (the receiver if not given) takes, so attaching it leaves nothing to refund. The series owner
+    /// pays no price. Without a receiver it's an upper bound using the worst-case storage (see `storage_cost_for_mint`).
+    pub fn total_mint_cost(
+        &self,
+        series_id: SeriesId,
+        receiver_id: Option<AccountId>,
+        minter_id: Option<AccountId>,
+    ) -> U128 {
+        let series = self.internal_get_series(series_id);
+        let Some(receiver_id) = receiver_id else {
+            return U128(series.price.unwrap_or(0) + self.mint_fee + self.internal_caller_storage_cost(&series));
+        };
+        let minter_id = minter_id.unwrap_or_else(|| receiver_id.clone());
 
-        //specify the token struct that contains the owner ID
+        let price = if minter_id == series.owner_id { 0 } else { series.price.unwrap_or(0) };
+        let storage_cost = match series.storage_payer {
+            StoragePayer::Caller => {
+                let bytes = self.internal_storage_for_next_mint(series_id, &series, &receiver_id, &minter_id);
+                Balance::from(bytes) * env::storage_byte_cost()
+            }
+            StoragePayer::Recipient | StoragePayer::SeriesOwner => 0,
+        };
+        U128(price + self.mint_fee + storage_cost)
+    }
+
+    /// Estimate the storage deposit needed to mint a single token so front-ends can prompt for the right amount.
+    /// The estimate assumes a worst-case token (longest account ID and token ID) minted to a first-time owner.
+    pub fn storage_cost_for_mint(&self) -> U128 {
         let token = Token {
-            // Series ID that the token belongs to
-            series_id: id.0,
-            //set the owner ID equal to the receiver ID passed into the function
-            owner_id: receiver_id,
-            //we set the approved account IDs to the default value (an empty map)
+            series_id: u64::MAX,
+            owner_id: AccountId::new_unchecked("a".repeat(64)),
             approved_account_ids: Default::default(),
-            //the next approval ID is set to 0
             next_approval_id: 0,
+            metadata_overrides: None,
+            paid_amount: 0,
+            minted_at: 0,
+            edition: u64::MAX,
+            storage_charge: Some(StorageCharge {
+                account_id: AccountId::new_unchecked("a".repeat(64)),
+                from_deposit: false,
+                bytes: 0,
+            }),
         };
+        let token_id = token_id_for_edition(u64::MAX, u64::MAX);
 
-        //insert the token ID and token struct and make sure that the token doesn't exist
-        require!(
-            self.tokens_by_id.insert(&token_id, &token).is_none(),
-            "Token already exists"
+        U128(Balance::from(bytes_for_token_mint(&token_id, &token, true, true, true)) * env::storage_byte_cost())
+    }
+}
+
+impl Contract {
+    //the series' configuration as a spec, whether it's visible or not
+    pub(crate) fn internal_export_series_spec(&self, series_id: SeriesId) -> SeriesSpec {
+        let series = self.internal_get_series(series_id);
+        SeriesSpec {
+            id: series_id,
+            metadata: series.metadata,
+            royalty: series.royalty,
+            price: series.price.map(U128),
+            description: series.description,
+            external_url: series.external_url,
+            visible: Some(series.visible),
+            collection_media: series.collection_media,
+            transferable: Some(series.transferable),
+            unique_per_account: Some(series.unique_per_account),
+            claim_only: Some(series.claim_only),
+            public_mint_enabled: Some(series.public_mint_enabled),
+            claim_deadline: series.claim_deadline,
+            on_mint_callback: series.on_mint_callback,
+            storage_payer: Some(series.storage_payer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+    use crate::nft_core::NonFungibleTokenCore;
+    use super::*;
+    use crate::test_utils::*;
+
+    fn spec(id: SeriesId) -> SeriesSpec {
+        series_spec(id, sample_metadata(&format!("Program {}", id)))
+    }
+
+    #[test]
+    #[should_panic(expected = "collection ID already exists")]
+    fn a_taken_series_id_cant_be_reused() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(1));
+        set_context(accounts(1), 1);
+        contract.nft_batch_burn(vec!["1:1".to_string()]);
+
+        //even with every token burned the series still exists, so its ID stays taken
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(series_spec(1, sample_metadata("Other program")), None);
+    }
+
+    #[test]
+    fn exported_spec_recreates_an_identical_series() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mut metadata = sample_metadata("Program 1");
+        metadata.copies = Some(50);
+        let royalty = HashMap::from([(accounts(3), 500)]);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                royalty: Some(royalty),
+                price: Some(U128(1_000)),
+                description: Some("A program".to_string()),
+                external_url: Some("https://example.com".to_string()),
+                visible: Some(false),
+                ..series_spec(1, metadata)
+            },
+            None,
         );
+        contract.set_series_transferable(1, true);
+        contract.set_series_public_mint(1, false);
+        contract.set_series_claim_deadline(1, Some(1_000));
 
-        //call the internal method for adding the token to the owner
-        self.internal_add_token_to_owner(&token.owner_id, &token_id);
-
-        // Construct the mint log as per the events standard.
-        let nft_mint_log: EventLog = EventLog {
-            // Standard name ("nep171").
-            standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
-            version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
-            event: EventLogVariant::NftMint(vec![NftMintLog {
-                // Owner of the token.
-                owner_id: token.owner_id.to_string(),
-                // Vector of token IDs that were minted.
-                token_ids: vec![token_id.to_string()],
-                // An optional memo to include.
-                memo: None,
-            }]),
-        };
+        let mut exported = contract.internal_export_series_spec(1);
+        exported.id = 2;
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.batch_create_series(vec![exported]);
 
-        // Log the serialized json.
-        env::log_str(&nft_mint_log.to_string());
+        let mut original = near_sdk::serde_json::to_value(contract.internal_export_series_spec(1)).unwrap();
+        original["id"] = 2.into();
+        assert_eq!(near_sdk::serde_json::to_value(contract.internal_export_series_spec(2)).unwrap(), original);
+        assert_eq!(contract.internal_export_series_spec(2).metadata.copies, Some(50));
+    }
 
-        //calculate the required storage which was the used - initial
-        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+    #[test]
+    fn batch_create_series_creates_every_series() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+
+        let ids = contract.batch_create_series((1..=10).map(spec).collect());
+
+        assert_eq!(ids, (1..=10).collect::<Vec<_>>());
+        for id in ids {
+            let series = contract.get_series_details(id).unwrap();
+            assert_eq!(series.metadata.title, Some(format!("Program {}", id)));
+            assert_eq!(series.owner_id, owner());
+        }
+    }
+
+    #[test]
+    fn batch_create_series_with_a_duplicate_id_creates_nothing() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+
+        let batch = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.batch_create_series(vec![spec(1), spec(2), spec(1)]);
+        }));
+
+        assert!(batch.unwrap_err().downcast_ref::<String>().unwrap().contains("Series ID 1 is in the batch twice"));
+        assert!(contract.get_series_details(1).is_none());
+    }
+
+    #[test]
+    fn create_series_with_same_external_id_is_idempotent() {
+        let mut contract = setup_contract();
 
-        // If there's some price for the token, we'll payout the series owner. Otherwise, refund the excess deposit for storage to the caller
-        if price_per_token > 0 {
-            payout_series_owner(required_storage_in_bytes, price_per_token, series.owner_id);
-        } else {
-            refund_deposit(required_storage_in_bytes);
+        set_context(owner(), STORAGE_DEPOSIT);
+        let first = contract.create_series_from_spec(series_spec(1, sample_metadata("Badge")), Some("cohort-1".to_string()));
+        // A retry with a different ID but the same external ID returns the original series
+        set_context(owner(), STORAGE_DEPOSIT);
+        let second = contract.create_series_from_spec(series_spec(2, sample_metadata("Badge")), Some("cohort-1".to_string()));
+
+        assert_eq!(first, 1);
+        assert_eq!(second, 1);
+        assert_eq!(contract.get_series_total_supply(), 1);
+        assert!(contract.get_series_details(2).is_none());
+    }
+
+    #[test]
+    fn storage_cost_for_mint_covers_a_real_mint() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_mint(U64(1), "a-fairly-long-receiver-account-name.near".parse().unwrap(), None, None, None, None);
+        let used = Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+
+        let estimate = contract.storage_cost_for_mint().0;
+        assert!(estimate > 0);
+        assert!(estimate >= used, "estimate {} is below the {} actually used", estimate, used);
+        assert!(estimate < 2 * used, "estimate {} is far above the {} actually used", estimate, used);
+    }
+
+    #[test]
+    fn total_mint_cost_is_enough_to_mint() {
+        let mut contract = setup_contract();
+        contract.set_mint_fee(U128(1_000));
+        contract.set_treasury(accounts(4));
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                price: Some(U128(STORAGE_DEPOSIT)),
+                ..series_spec(1, sample_metadata("Paid"))
+            },
+            None,
+        );
+
+        assert_eq!(contract.total_mint_cost(1, None, None).0, STORAGE_DEPOSIT + 1_000 + contract.storage_cost_for_mint().0);
+
+        //a first mint to the account, then one to an account that already holds tokens of the series
+        for receiver_id in [accounts(1), accounts(1)] {
+            let total = contract.total_mint_cost(1, Some(receiver_id.clone()), None).0;
+            set_context(receiver_id.clone(), total);
+            let token_id = contract.nft_mint(U64(1), receiver_id.clone(), None, None, None, None);
+
+            assert_eq!(contract.nft_token(token_id).unwrap().owner_id, receiver_id);
+            assert_eq!(transfers_to(&owner()), vec![STORAGE_DEPOSIT]);
+            assert_eq!(transfers_to(&accounts(4)), vec![1_000]);
+            //exactly that deposit is used up, so nothing is refunded
+            assert!(transfers_to(&receiver_id).is_empty());
+        }
+    }
+
+    //a paid series whose storage comes out of a deposit costs exactly its price, with nothing left to refund
+    fn assert_exact_price_mints(storage_payer: StoragePayer) {
+        let mut contract = setup_contract();
+        create_paid_series(&mut contract, 1);
+        contract.set_series_storage_payer(1, storage_payer);
+        for account_id in [owner(), accounts(1)] {
+            set_context(account_id, STORAGE_DEPOSIT);
+            contract.deposit_storage(None);
+            contract.approve_storage_payment(1);
         }
+
+        let total = contract.total_mint_cost(1, Some(accounts(1)), None).0;
+        assert_eq!(total, PRICE);
+        set_context(accounts(1), total);
+        let token_id = contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+        assert_eq!(transfers_to(&owner()), vec![PRICE]);
+        assert!(transfers_to(&accounts(1)).is_empty());
+    }
+
+    #[test]
+    fn total_mint_cost_is_enough_when_the_recipient_pays_storage() {
+        assert_exact_price_mints(StoragePayer::Recipient);
+    }
+
+    #[test]
+    fn total_mint_cost_is_enough_when_the_series_owner_pays_storage() {
+        assert_exact_price_mints(StoragePayer::SeriesOwner);
+    }
+
+    #[test]
+    fn total_mint_cost_for_a_receiver_matches_the_storage_used() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let receiver_id: AccountId = "a-fairly-long-receiver-account-name.near".parse().unwrap();
+
+        let total = contract.total_mint_cost(1, Some(receiver_id.clone()), Some(owner())).0;
+        set_context(owner(), STORAGE_DEPOSIT);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_mint(U64(1), receiver_id, None, None, None, None);
+
+        assert_eq!(total, Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost());
+    }
+
+    #[test]
+    fn total_mint_cost_leaves_out_storage_paid_by_someone_else() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_storage_payer(1, StoragePayer::SeriesOwner);
+
+        assert_eq!(contract.total_mint_cost(1, None, None).0, 0);
+        assert_eq!(contract.total_mint_cost(1, Some(accounts(1)), None).0, 0);
+    }
+
+    #[test]
+    fn mint_with_overrides_replaces_only_the_set_fields() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mut metadata = sample_metadata("Contributor");
+        metadata.description = Some("Series description".to_string());
+        contract.create_series_from_spec(series_spec(1, metadata), None);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let token_id = contract.nft_mint_with_overrides(U64(1), accounts(1), sample_metadata("Contributor: Alice"), None);
+
+        let token = contract.nft_token(token_id).unwrap();
+        assert_eq!(token.metadata.title.as_deref(), Some("Contributor: Alice"));
+        assert_eq!(token.metadata.description.as_deref(), Some("Series description"));
+    }
+
+    const PRICE: Balance = 5 * STORAGE_DEPOSIT;
+
+    fn create_paid_series(contract: &mut Contract, id: SeriesId) {
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                price: Some(U128(PRICE)),
+                ..series_spec(id, sample_metadata("Paid"))
+            },
+            None,
+        );
+    }
+
+    #[test]
+    fn series_owner_mints_paid_series_without_paying_the_price() {
+        let mut contract = setup_contract();
+        create_paid_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Need to attach at least enough to cover price")]
+    fn regular_user_must_pay_the_price() {
+        let mut contract = setup_contract();
+        create_paid_series(&mut contract, 1);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+    }
+
+    #[test]
+    fn unique_series_mints_once_per_account() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_unique_per_account(1, true);
+
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(2));
+
+        assert_eq!(contract.nft_supply_for_series(1).0, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account already holds this badge")]
+    fn unique_series_rejects_a_second_mint_to_the_same_account() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_unique_per_account(1, true);
+
+        mint_to(&mut contract, 1, accounts(1));
+        mint_to(&mut contract, 1, accounts(1));
+    }
+
+    #[test]
+    fn pausing_a_series_only_blocks_its_own_mints() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        contract.set_series_minting_paused(1, true);
+
+        mint_to(&mut contract, 2, accounts(1));
+        let paused_mint = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mint_to(&mut contract, 1, accounts(1));
+        }));
+
+        assert!(paused_mint.is_err());
+        assert_eq!(contract.nft_supply_for_series(2).0, 1);
+        assert_eq!(contract.nft_supply_for_series(1).0, 0);
+    }
+
+    #[test]
+    fn resumed_series_mints_again() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_minting_paused(1, true);
+        set_context(owner(), 0);
+        contract.set_series_minting_paused(1, false);
+
+        mint_to(&mut contract, 1, accounts(1));
+
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Series minting paused")]
+    fn paused_series_rejects_mints() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_minting_paused(1, true);
+
+        mint_to(&mut contract, 1, accounts(1));
+    }
+
+    #[test]
+    fn public_mint_is_blocked_until_enabled() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        assert!(!contract.get_series_details(1).unwrap().public_mint_enabled);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        let early_mint = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        }));
+        assert!(early_mint.unwrap_err().downcast_ref::<String>().unwrap().contains("Not approved minter"));
+
+        set_context(owner(), 0);
+        contract.set_series_public_mint(1, true);
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        let token_id = contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1));
+    }
+
+    #[test]
+    fn claims_work_while_public_mint_is_closed() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_claim_allowance(1, accounts(1), 1);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_claim(1);
+
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: Public minting isn't open for this series")]
+    fn closing_public_mint_stops_sales_of_a_priced_series() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                price: Some(U128(STORAGE_DEPOSIT / 10)),
+                ..series_spec(1, sample_metadata("Paid"))
+            },
+            None,
+        );
+        assert!(contract.get_series_details(1).unwrap().public_mint_enabled);
+        contract.set_series_public_mint(1, false);
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+    }
+
+    fn paid_amount(contract: &Contract, token_id: TokenId) -> String {
+        let extra = contract.nft_token(token_id).unwrap().metadata.extra.unwrap();
+        let extra: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(&extra).unwrap();
+        extra["paid_amount"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn mint_records_the_paid_amount() {
+        let mut contract = setup_contract();
+        create_paid_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+
+        set_context(accounts(1), PRICE + STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        let free_token_id = mint_to(&mut contract, 2, accounts(1));
+
+        assert_eq!(paid_amount(&contract, "1:1".to_string()), PRICE.to_string());
+        assert_eq!(paid_amount(&contract, free_token_id), "0");
+    }
+
+    #[test]
+    fn reassigned_token_resolves_the_new_series_metadata() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(series_spec(2, sample_metadata("Mentor")), None);
+        mint_to(&mut contract, 2, accounts(2));
+        mint_to(&mut contract, 1, accounts(1));
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(owner(), 0);
+        contract.reassign_token_series(token_id.clone(), U64(2));
+
+        let token = contract.nft_token(token_id.clone()).unwrap();
+        assert_eq!(token.series_id, 2);
+        assert_eq!(token.metadata.title.as_deref(), Some("Mentor - 2"));
+        assert_eq!(contract.token_ids_for_series(2, None, None), vec!["2:1".to_string(), token_id]);
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+        assert!(near_sdk::test_utils::get_logs()[0].contains(r#""event":"nft_metadata_update""#));
+    }
+
+    fn setup_reassign() -> (Contract, TokenId) {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+        set_context(owner(), 0);
+        (contract, token_id)
+    }
+
+    #[test]
+    fn reassign_accepts_a_locked_destination() {
+        let (mut contract, token_id) = setup_reassign();
+        contract.lock_series(2);
+        contract.reassign_token_series(token_id.clone(), U64(2));
+        assert_eq!(contract.nft_token(token_id).unwrap().series_id, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Mint is disabled")]
+    fn reassign_rejects_while_minting_is_disabled() {
+        let (mut contract, token_id) = setup_reassign();
+        contract.set_operation_enabled(Operation::Mint, false);
+        contract.reassign_token_series(token_id, U64(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED: Series minting paused")]
+    fn reassign_rejects_a_paused_destination() {
+        let (mut contract, token_id) = setup_reassign();
+        contract.set_series_minting_paused(2, true);
+        contract.reassign_token_series(token_id, U64(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't eligible to receive this series")]
+    fn reassign_requires_the_holder_to_be_eligible_for_the_destination() {
+        let (mut contract, token_id) = setup_reassign();
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.add_eligible_receivers(2, vec![accounts(2)]);
+        set_context(owner(), 0);
+        contract.reassign_token_series(token_id, U64(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Account already holds this badge")]
+    fn reassign_cannot_give_a_second_token_of_a_unique_series() {
+        let (mut contract, token_id) = setup_reassign();
+        contract.set_series_unique_per_account(2, true);
+        mint_to(&mut contract, 2, accounts(1));
+        set_context(owner(), 0);
+        contract.reassign_token_series(token_id, U64(2));
+    }
+
+    #[test]
+    fn replacing_a_token_keeps_its_edition_and_mint_time() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        mint_to(&mut contract, 1, accounts(2));
+        let mut context = set_context(owner(), STORAGE_DEPOSIT);
+        testing_env!(context.block_timestamp(5_000 * 1_000_000).build());
+        let token_id = contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        let mut context = set_context(owner(), STORAGE_DEPOSIT);
+        testing_env!(context.block_timestamp(9_000 * 1_000_000).build());
+        let mut overrides = sample_metadata("Fixed badge");
+        overrides.media = Some("https://example.com/fixed.png".to_string());
+        contract.replace_token(token_id.clone(), Some(overrides));
+
+        let token = contract.tokens_by_id.get(&token_id).unwrap();
+        assert_eq!(token.edition, 2);
+        assert_eq!(token.minted_at, 5_000);
+        assert_eq!(token.owner_id, accounts(1));
+        let metadata = contract.nft_token(token_id.clone()).unwrap().metadata;
+        assert_eq!(metadata.media.as_deref(), Some("https://example.com/fixed.png"));
+        assert!(near_sdk::test_utils::get_logs().last().unwrap().contains(r#""event":"nft_metadata_update""#));
+        assert_eq!(contract.token_provenance(token_id).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED")]
+    fn only_the_series_owner_can_replace_a_token() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), STORAGE_DEPOSIT);
+        contract.replace_token(token_id, Some(sample_metadata("Mine now")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner or the owner of both series can move a token between them")]
+    fn token_holder_cannot_reassign_its_series() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        create_sample_series(&mut contract, 2);
+        let token_id = mint_to(&mut contract, 1, accounts(1));
+
+        set_context(accounts(1), 0);
+        contract.reassign_token_series(token_id, U64(2));
+    }
+
+    #[test]
+    fn cloned_series_copies_the_config_but_not_the_tokens() {
+        let mut contract = setup_contract();
+        let mut metadata = sample_metadata("Cohort");
+        metadata.copies = Some(30);
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                royalty: Some(HashMap::from([(accounts(3), 500)])),
+                price: Some(U128(PRICE)),
+                ..series_spec(1, metadata)
+            },
+            None,
+        );
+        contract.set_series_unique_per_account(1, true);
+        contract.set_series_public_mint(1, false);
+        contract.set_series_claim_deadline(1, Some(9_000));
+        contract.set_series_mint_callback(1, Some(accounts(4)));
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        contract.set_series_storage_payer(1, StoragePayer::SeriesOwner);
+        contract.add_approved_creator(accounts(2));
+
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        contract.clone_series(U64(1), U64(2));
+        assert_eq!(contract.get_series_details(2).unwrap().metadata.copies, Some(30));
+        contract.update_series_metadata(2, sample_metadata("Next cohort"));
+
+        let source = contract.get_series_details(1).unwrap();
+        let clone = contract.get_series_details(2).unwrap();
+        assert_eq!(clone.owner_id, accounts(2));
+        assert_eq!(clone.royalty, source.royalty);
+        assert!(clone.unique_per_account);
+        assert_eq!(contract.series_by_id.get(&2).unwrap().price, Some(PRICE));
+        let exported = contract.export_series_spec(2);
+        assert_eq!(exported.public_mint_enabled, Some(false));
+        assert_eq!(exported.claim_deadline, Some(9_000));
+        assert_eq!(exported.on_mint_callback, Some(accounts(4)));
+        assert_eq!(exported.storage_payer, Some(StoragePayer::SeriesOwner));
+        assert_eq!(contract.nft_supply_for_series(2).0, 0);
+        assert_eq!(source.metadata.title.as_deref(), Some("Cohort"));
+        assert_eq!(source.metadata.copies, Some(30));
+    }
+
+    #[test]
+    fn hidden_series_can_only_be_cloned_by_their_owner() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_visible(1, false);
+        contract.add_approved_creator(accounts(2));
+
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        let clone = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.clone_series(U64(1), U64(2));
+        }));
+        assert!(clone.unwrap_err().downcast_ref::<String>().unwrap().contains("ERR_SERIES_NOT_FOUND"));
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.clone_series(U64(1), U64(3));
+        assert!(!contract.get_series_for_owner(owner(), None, None)[1].visible);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SERIES_NOT_FOUND")]
+    fn hidden_series_cant_be_exported() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_visible(1, false);
+
+        contract.export_series_spec(1);
+    }
+
+    #[test]
+    fn batch_mint_mints_to_every_receiver() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let token_ids = contract.nft_batch_mint(U64(1), vec![accounts(1), accounts(2), accounts(3)], None);
+
+        assert_eq!(token_ids, vec!["1:1", "1:2", "1:3"]);
+        assert_eq!(contract.nft_token("1:3".to_string()).unwrap().owner_id, accounts(3));
+    }
+
+    #[test]
+    fn batch_mint_emits_one_event_with_an_entry_per_token() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_batch_mint(U64(1), vec![accounts(1), accounts(2), accounts(3)], None);
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1);
+        let event: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(logs[0].trim_start_matches("EVENT_JSON:")).unwrap();
+        assert_eq!(event["event"], "nft_mint");
+        assert_eq!(event["data"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn under_funded_batch_mint_writes_nothing() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        let receiver_ids: Vec<AccountId> = (0..100).map(|i| format!("receiver-{}.near", i).parse().unwrap()).collect();
+        let required = 100 * contract.storage_cost_for_mint().0;
+
+        set_context(owner(), required - 1);
+        let storage_before = env::storage_usage();
+        let batch = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_batch_mint(U64(1), receiver_ids, None);
+        }));
+
+        assert!(batch.unwrap_err().downcast_ref::<String>().unwrap().contains("to mint 100 tokens"));
+        assert_eq!(env::storage_usage(), storage_before);
+        assert_eq!(contract.nft_supply_for_series(1).0, 0);
+    }
+
+    #[test]
+    fn batch_failing_on_its_last_receiver_leaves_every_counter_unchanged() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_unique_per_account(1, true);
+        mint_to(&mut contract, 1, accounts(3));
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let storage_before = env::storage_usage();
+        let supply_before = contract.supply_breakdown();
+        let batch = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_batch_mint(U64(1), vec![accounts(1), accounts(2), accounts(1)], None);
+        }));
+
+        assert!(batch.unwrap_err().downcast_ref::<String>().unwrap().contains("Account already holds this badge"));
+        assert_eq!(env::storage_usage(), storage_before);
+        assert_eq!(contract.supply_breakdown(), supply_before);
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+        assert_eq!(contract.nft_supply_for_owner(accounts(1)).0, 0);
+        assert_eq!(contract.series_unique_owners(1), 1);
+        assert_eq!(contract.series_by_id.get(&1).unwrap().editions_minted, 1);
+        assert!(contract.nft_token("1:2".to_string()).is_none());
+    }
+
+    #[test]
+    fn paid_mint_refunds_the_overpayment_to_refund_to() {
+        let mut contract = setup_contract();
+        create_paid_series(&mut contract, 1);
+        let relayer = accounts(3);
+        let buyer = accounts(4);
+
+        set_context(relayer.clone(), PRICE + STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), buyer.clone(), Some(buyer.clone()), None, None, None);
+
+        assert_eq!(transfers_to(&owner()), vec![PRICE]);
+        let refunds = transfers_to(&buyer);
+        assert_eq!(refunds.len(), 1);
+        assert!(refunds[0] > 0 && refunds[0] < STORAGE_DEPOSIT);
+        assert!(transfers_to(&relayer).is_empty());
+    }
+
+    #[test]
+    fn paid_mint_ignores_the_royalty_map() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        let royalty = HashMap::from([(accounts(3), 2_500)]);
+        contract.create_series_from_spec(
+            SeriesSpec {
+                royalty: Some(royalty),
+                price: Some(U128(PRICE)),
+                ..series_spec(1, sample_metadata("Paid"))
+            },
+            None,
+        );
+
+        set_context(accounts(1), PRICE + STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        assert_eq!(transfers_to(&owner()), vec![PRICE]);
+        assert!(transfers_to(&accounts(3)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Title can be at most 256 characters long")]
+    fn over_long_title_is_rejected() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.create_series_from_spec(series_spec(1, sample_metadata(&"t".repeat(257))), None);
+    }
+
+    #[test]
+    fn boundary_length_title_and_description_are_accepted() {
+        let mut contract = setup_contract();
+        set_context(owner(), STORAGE_DEPOSIT);
+        // Multi-byte characters count once each
+        let mut metadata = sample_metadata(&"é".repeat(256));
+        metadata.description = Some("d".repeat(2000));
+        contract.create_series_from_spec(series_spec(1, metadata.clone()), None);
+
+        metadata.description = Some("d".repeat(2001));
+        let update = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.update_series_metadata(1, metadata);
+        }));
+
+        assert_eq!(contract.get_series_details(1).unwrap().metadata.title.unwrap().chars().count(), 256);
+        assert!(update.unwrap_err().downcast_ref::<String>().unwrap().contains("Description can be at most 2000"));
+    }
+
+    #[test]
+    fn retried_mint_with_the_same_request_id_mints_once() {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let first = contract.nft_mint(U64(1), accounts(1), None, Some("req-1".to_string()), None, None);
+        set_context(owner(), STORAGE_DEPOSIT);
+        let retry = contract.nft_mint(U64(1), accounts(1), None, Some("req-1".to_string()), None, None);
+
+        assert_eq!(first, retry);
+        assert_eq!(contract.nft_supply_for_series(1).0, 1);
+        assert_eq!(transfers_to(&owner()), vec![STORAGE_DEPOSIT]);
+        // The same request ID from another minter is a different request
+        contract.add_approved_minter(accounts(2));
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, Some("req-1".to_string()), None, None);
+        assert_eq!(contract.nft_supply_for_series(1).0, 2);
     }
 }
@@ -1,8 +1,13 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{env, near_bindgen};
+use near_sdk::collections::Vector;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::StorageKey;
 
 // Define the token structure
-#[derive(BorshSerialize, BorshDeserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
 pub struct Token {
     pub id: u64,
     pub series_id: u64,
@@ -14,65 +19,68 @@ pub struct Token {
 }
 
 // Define the series structure
-#[derive(BorshSerialize, BorshDeserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
 pub struct Series {
     pub id: u64,
     pub name: String,
 }
 
-// Define the contract
-#[near_bindgen]
-#[derive(Default, BorshDeserialize, BorshSerialize)]
+/// An open collection of tokens and series.
+/// Its collections live under their own `StorageKey` prefixes so it can be stored next to,
+/// or migrated into, the main contract state without colliding with it.
+#[derive(BorshDeserialize, BorshSerialize)]
 pub struct OpenCollection {
-    pub tokens: Vec<Token>,
-    pub series: Vec<Series>,
+    pub tokens: Vector<Token>,
+    pub series: Vector<Series>,
 }
 
-#[near_bindgen]
 impl OpenCollection {
+    /// Initialize an empty collection under the `OpenCollection*` storage prefixes.
+    //no `Default`: a default value would hide that every instance shares the same prefixes
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            tokens: Vector::new(StorageKey::OpenCollectionTokens),
+            series: Vector::new(StorageKey::OpenCollectionSeries),
+        }
+    }
+
     // Mint a new token with specified details
-    pub fn mint_token(
-        &mut self,
-        id: u64,
-        series_id: u64,
-        owner: String,
-        image_url: String,
-        reference: String,
-        title: String,
-        description: String,
-    ) {
-        let new_token = Token {
-            id,
-            series_id,
-            owner,
-            image_url,
-            reference,
-            title,
-            description,
-        };
-        self.tokens.push(new_token);
+    pub fn mint_token(&mut self, token: Token) {
+        self.tokens.push(&token);
     }
 
     // Create a new series
     pub fn create_series(&mut self, id: u64, name: String) {
         let new_series = Series { id, name };
-        self.series.push(new_series);
+        self.series.push(&new_series);
     }
 
     // Get the token details by ID
     pub fn get_token(&self, id: u64) -> Option<Token> {
-        self.tokens.iter().find(|token| token.id == id).cloned()
+        self.tokens.iter().find(|token| token.id == id)
     }
 
     // Get the series details by ID
     pub fn get_series(&self, id: u64) -> Option<Series> {
-        self.series.iter().find(|series| series.id == id).cloned()
+        self.series.iter().find(|series| series.id == id)
+    }
+
+    /// Paginate through the tokens in mint order. Out of range indexes return an empty page.
+    pub fn get_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        page(&self.tokens, from_index, limit)
+    }
+
+    /// Paginate through the series in creation order. Out of range indexes return an empty page.
+    pub fn get_all_series(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Series> {
+        page(&self.series, from_index, limit)
     }
 
     // Update the series name by ID
     pub fn update_series_name(&mut self, id: u64, name: String) {
-        if let Some(series) = self.series.iter_mut().find(|series| series.id == id) {
-            series.name = name;
+        if let Some(index) = self.series.iter().position(|series| series.id == id) {
+            self.series.replace(index as u64, &Series { id, name });
         }
     }
 
@@ -86,12 +94,87 @@ impl OpenCollection {
         title: String,
         description: String,
     ) {
-        if let Some(token) = self.tokens.iter_mut().find(|token| token.id == id) {
-            token.owner = owner;
-            token.image_url = image_url;
-            token.reference = reference;
-            token.title = title;
-            token.description = description;
+        if let Some((index, token)) = self.tokens.iter().enumerate().find(|(_, token)| token.id == id) {
+            self.tokens.replace(
+                index as u64,
+                &Token {
+                    owner,
+                    image_url,
+                    reference,
+                    title,
+                    description,
+                    ..token
+                },
+            );
+        }
+    }
+}
+
+//slice a vector, clamping the bounds to its length. If we didn't specify a limit, use 50
+fn page<T: BorshSerialize + BorshDeserialize>(
+    items: &Vector<T>,
+    from_index: Option<U128>,
+    limit: Option<u64>,
+) -> Vec<T> {
+    let start = u128::from(from_index.unwrap_or(U128(0))).min(items.len() as u128) as u64;
+    let end = start.saturating_add(limit.unwrap_or(50)).min(items.len());
+    (start..end).filter_map(|index| items.get(index)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn new_collection_mints_under_its_own_prefix() {
+        let mut contract = setup_contract();
+        let mut collection = OpenCollection::new();
+
+        collection.create_series(1, "Hackathon".to_string());
+        collection.mint_token(Token {
+            id: 7,
+            series_id: 1,
+            owner: "alice.near".to_string(),
+            image_url: "https://example.com/7.png".to_string(),
+            reference: String::new(),
+            title: "Winner".to_string(),
+            description: String::new(),
+        });
+
+        assert_eq!(collection.get_series(1).unwrap().name, "Hackathon");
+        assert_eq!(collection.get_token(7).unwrap().owner, "alice.near");
+        // The main contract's collections are untouched
+        create_sample_series(&mut contract, 1);
+        assert_eq!(contract.get_series_total_supply(), 1);
+        assert_eq!(collection.get_token(7).unwrap().title, "Winner");
+    }
+
+    #[test]
+    fn tokens_and_series_are_paginated() {
+        set_context(owner(), 0);
+        let mut collection = OpenCollection::new();
+        for id in 0..5 {
+            collection.create_series(id, format!("Series {}", id));
+            collection.mint_token(Token {
+                id,
+                series_id: id,
+                owner: "alice.near".to_string(),
+                image_url: String::new(),
+                reference: String::new(),
+                title: String::new(),
+                description: String::new(),
+            });
         }
+
+        let ids = |tokens: Vec<Token>| tokens.into_iter().map(|token| token.id).collect::<Vec<_>>();
+        assert_eq!(ids(collection.get_tokens(None, Some(2))), vec![0, 1]);
+        assert_eq!(ids(collection.get_tokens(Some(U128(2)), Some(2))), vec![2, 3]);
+        assert_eq!(ids(collection.get_tokens(Some(U128(4)), Some(2))), vec![4]);
+        assert!(collection.get_tokens(Some(U128(10)), None).is_empty());
+        assert_eq!(collection.get_tokens(None, None).len(), 5);
+
+        let series = collection.get_all_series(Some(U128(3)), Some(u64::MAX));
+        assert_eq!(series.into_iter().map(|series| series.id).collect::<Vec<_>>(), vec![3, 4]);
     }
 }
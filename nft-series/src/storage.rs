@@ -0,0 +1,307 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+/// Who pays for the storage a mint of the series takes up
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[serde(crate = "near_sdk::serde")]
+pub enum StoragePayer {
+    /// Each receiver pays for their own token from their storage deposit, once they approve it
+    Recipient,
+    /// The series owner pays from their storage deposit, e.g. for sponsored programs, once they approve it
+    SeriesOwner,
+    /// The minting account pays from the attached deposit
+    #[default]
+    Caller,
+}
+
+/// Who paid for a token's storage when it was minted, and how many bytes, so burning it refunds them
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageCharge {
+    pub account_id: AccountId,
+    /// Whether it came out of the account's storage deposit (and goes back there) rather than an attached deposit
+    pub from_deposit: bool,
+    pub bytes: u64,
+}
+
+//storage refunds owed for removed tokens, gathered so a batch pays each account once
+#[derive(Default)]
+pub(crate) struct StorageRefunds {
+    //credited back to storage deposits
+    deposits: BTreeMap<AccountId, Balance>,
+    //transferred back to the accounts that attached the deposit
+    transfers: BTreeMap<AccountId, Balance>,
+}
+
+impl StorageRefunds {
+    pub(crate) fn add(&mut self, charge: &StorageCharge, bytes: u64) {
+        let refunds = if charge.from_deposit { &mut self.deposits } else { &mut self.transfers };
+        *refunds.entry(charge.account_id.clone()).or_insert(0) += Balance::from(bytes) * env::storage_byte_cost();
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add the attached deposit to `account_id`'s (or the caller's) storage deposit, which pays for mints of series
+    /// whose storage payer is the recipient or the series owner. The first deposit for an account also pays for
+    /// its own record. Returns the account's new balance.
+    #[payable]
+    pub fn deposit_storage(&mut self, account_id: Option<AccountId>) -> U128 {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let initial_storage_usage = env::storage_usage();
+
+        let balance = self.storage_deposits.get(&account_id).unwrap_or(0);
+        self.storage_deposits.insert(&account_id, &balance);
+        let record_cost = env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage);
+        require!(
+            env::attached_deposit() > record_cost,
+            ContractError::Underfunded.message(&format!("Must attach more than {} yoctoNEAR", record_cost))
+        );
+
+        let balance = balance + env::attached_deposit() - record_cost;
+        self.storage_deposits.insert(&account_id, &balance);
+        self.storage_deposits_total += env::attached_deposit() - record_cost;
+        U128(balance)
+    }
+
+    /// Withdraw `amount` (or everything) from the caller's storage deposit. Returns what was withdrawn.
+    #[payable]
+    pub fn withdraw_storage(&mut self, amount: Option<U128>) -> U128 {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = self.storage_deposits.get(&account_id).unwrap_or(0);
+        let amount = amount.map_or(balance, |amount| amount.0);
+        require!(amount <= balance, ContractError::Underfunded.message("Can't withdraw more than the storage deposit"));
+
+        self.storage_deposits.insert(&account_id, &(balance - amount));
+        self.storage_deposits_total -= amount;
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+        U128(amount)
+    }
+
+    /// Agree to pay, out of the caller's storage deposit, for mints of the series that other accounts make when its
+    /// storage payer is the recipient or the series owner. Without this only the caller's own mints are charged to
+    /// their deposit. Attach the storage for the approval; the excess is refunded.
+    #[payable]
+    pub fn approve_storage_payment(&mut self, series_id: SeriesId) {
+        self.internal_get_series(series_id);
+        let initial_storage_usage = env::storage_usage();
+        self.storage_payment_approvals.insert(&(series_id, env::predecessor_account_id()));
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+    }
+
+    /// Withdraw the caller's agreement to pay for other accounts' mints of the series
+    pub fn revoke_storage_payment(&mut self, series_id: SeriesId) {
+        self.storage_payment_approvals.remove(&(series_id, env::predecessor_account_id()));
+    }
+
+    /// Whether `account_id`'s storage deposit pays for other accounts' mints of the series
+    pub fn is_storage_payment_approved(&self, series_id: SeriesId, account_id: AccountId) -> bool {
+        self.storage_payment_approvals.contains(&(series_id, account_id))
+    }
+
+    /// How much of `account_id`'s storage deposit is left to pay for mints
+    pub fn storage_deposit_of(&self, account_id: AccountId) -> U128 {
+        U128(self.storage_deposits.get(&account_id).unwrap_or(0))
+    }
+
+    /// Choose who pays for the storage of the series' mints. A storage deposit only pays for other callers' mints
+    /// after its account calls `approve_storage_payment`. Only the series owner can call this.
+    pub fn set_series_storage_payer(&mut self, series_id: SeriesId, storage_payer: StoragePayer) {
+        let mut series = self.internal_get_series(series_id);
+        self.assert_series_owner(&series);
+        series.update_storage_payer(storage_payer);
+        self.series_by_id.insert(&series_id, &series);
+
+        // Emit the event
+        EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog::default()]).emit();
+    }
+}
+
+impl Contract {
+    //the storage deposit the minting account attaches per token: none if the recipient or series owner pays for it
+    pub(crate) fn internal_caller_storage_cost(&self, series: &Series) -> Balance {
+        match series.storage_payer {
+            StoragePayer::Caller => self.storage_cost_for_mint().0,
+            StoragePayer::Recipient | StoragePayer::SeriesOwner => 0,
+        }
+    }
+
+    //panics unless `account_id` mints the series themselves or agreed to pay for its mints from their storage deposit
+    pub(crate) fn assert_storage_payment_approved(&self, series_id: SeriesId, account_id: &AccountId) {
+        if account_id != &env::predecessor_account_id()
+            && !self.storage_payment_approvals.contains(&(series_id, account_id.clone()))
+        {
+            ContractError::Unauthorized.panic(&format!(
+                "{} hasn't approved paying for the storage of this series' mints",
+                account_id
+            ));
+        }
+    }
+
+    //take the cost of `storage_used` bytes out of `account_id`'s storage deposit, panicking if it doesn't cover it
+    pub(crate) fn internal_charge_storage_deposit(&mut self, account_id: &AccountId, storage_used: u64) {
+        let cost = env::storage_byte_cost() * Balance::from(storage_used);
+        let balance = self.storage_deposits.get(account_id).unwrap_or(0);
+        require!(
+            balance >= cost,
+            ContractError::Underfunded.message(&format!(
+                "{} needs {} yoctoNEAR of storage deposit to pay for this mint",
+                account_id, cost
+            ))
+        );
+        self.storage_deposits.insert(account_id, &(balance - cost));
+        self.storage_deposits_total -= cost;
+    }
+
+    //pay out the refunds gathered while removing tokens
+    pub(crate) fn internal_pay_storage_refunds(&mut self, refunds: StorageRefunds) {
+        for (account_id, amount) in refunds.deposits {
+            let balance = self.storage_deposits.get(&account_id).unwrap_or(0);
+            self.storage_deposits.insert(&account_id, &(balance + amount));
+            self.storage_deposits_total += amount;
+        }
+        for (account_id, amount) in refunds.transfers {
+            if amount > 0 {
+                Promise::new(account_id).transfer(amount);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::accounts;
+
+    fn setup_with_payer(storage_payer: StoragePayer) -> Contract {
+        let mut contract = setup_contract();
+        create_sample_series(&mut contract, 1);
+        contract.set_series_storage_payer(1, storage_payer);
+        for account_id in [owner(), accounts(1)] {
+            set_context(account_id, STORAGE_DEPOSIT);
+            contract.deposit_storage(None);
+            contract.approve_storage_payment(1);
+        }
+        contract
+    }
+
+    fn deposits(contract: &Contract) -> (Balance, Balance) {
+        (contract.storage_deposit_of(owner()).0, contract.storage_deposit_of(accounts(1)).0)
+    }
+
+    #[test]
+    fn caller_pays_from_the_attached_deposit() {
+        let mut contract = setup_with_payer(StoragePayer::Caller);
+        let before = deposits(&contract);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        assert_eq!(deposits(&contract), before);
+        assert!(transfers_to(&owner())[0] < STORAGE_DEPOSIT);
+    }
+
+    #[test]
+    fn series_owner_pays_from_their_storage_deposit() {
+        let mut contract = setup_with_payer(StoragePayer::SeriesOwner);
+        let (owner_before, recipient_before) = deposits(&contract);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        let (owner_after, recipient_after) = deposits(&contract);
+        assert!(owner_after < owner_before);
+        assert_eq!(recipient_after, recipient_before);
+        //nothing came out of the attached deposit
+        assert_eq!(transfers_to(&owner()), vec![STORAGE_DEPOSIT]);
+    }
+
+    #[test]
+    fn recipient_pays_from_their_storage_deposit() {
+        let mut contract = setup_with_payer(StoragePayer::Recipient);
+        let (owner_before, recipient_before) = deposits(&contract);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+
+        let (owner_after, recipient_after) = deposits(&contract);
+        assert_eq!(owner_after, owner_before);
+        assert!(recipient_after < recipient_before);
+        assert_eq!(transfers_to(&owner()), vec![STORAGE_DEPOSIT]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNDERFUNDED: charlie needs")]
+    fn recipient_without_a_storage_deposit_cant_be_minted_to() {
+        let mut contract = setup_with_payer(StoragePayer::Recipient);
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        contract.approve_storage_payment(1);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(2), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNAUTHORIZED: charlie hasn't approved paying for the storage of this series' mints")]
+    fn recipient_must_approve_paying_for_storage() {
+        let mut contract = setup_with_payer(StoragePayer::Recipient);
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        contract.deposit_storage(None);
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        contract.nft_mint(U64(1), accounts(2), None, None, None, None);
+    }
+
+    #[test]
+    fn revoking_the_storage_payment_approval_stops_the_charges() {
+        let mut contract = setup_with_payer(StoragePayer::Recipient);
+        assert!(contract.is_storage_payment_approved(1, accounts(1)));
+
+        set_context(accounts(1), 0);
+        contract.revoke_storage_payment(1);
+        assert!(!contract.is_storage_payment_approved(1, accounts(1)));
+
+        set_context(owner(), STORAGE_DEPOSIT);
+        let mint = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        }));
+        assert!(mint.unwrap_err().downcast_ref::<String>().unwrap().contains("ERR_UNAUTHORIZED"));
+    }
+
+    #[test]
+    fn unused_storage_deposit_can_be_withdrawn() {
+        let mut contract = setup_with_payer(StoragePayer::Caller);
+        let balance = contract.storage_deposit_of(accounts(1));
+
+        set_context(accounts(1), 1);
+        assert_eq!(contract.withdraw_storage(None), balance);
+
+        assert_eq!(contract.storage_deposit_of(accounts(1)).0, 0);
+        assert_eq!(transfers_to(&accounts(1)), vec![balance.0]);
+    }
+
+    #[test]
+    fn burning_returns_the_storage_to_the_paying_deposit() {
+        let mut contract = setup_with_payer(StoragePayer::Recipient);
+        let before = contract.storage_deposit_of(accounts(1)).0;
+        set_context(owner(), STORAGE_DEPOSIT);
+        let token_id = contract.nft_mint(U64(1), accounts(1), None, None, None, None);
+        let charged = before - contract.storage_deposit_of(accounts(1)).0;
+        let total_before_burn = contract.storage_deposits_total;
+
+        set_context(owner(), 1);
+        contract.nft_revoke_badge(token_id, RevokeReason::Fraud, None);
+
+        //all but the burn log entry the burn leaves behind comes back
+        let refunded = contract.storage_deposit_of(accounts(1)).0 + charged - before;
+        assert!(refunded > 0 && refunded <= charged);
+        assert_eq!(contract.storage_deposits_total, total_before_burn + refunded);
+        assert!(transfers_to(&accounts(1)).is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::*;
+
+/// A token owner's standing offer to trade their token for one specific other token
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SwapOffer {
+    //the owner who made the offer and paid for its storage. The offer is dropped once the token changes hands
+    pub owner_id: AccountId,
+    pub wanted_token_id: TokenId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Offer to trade the caller's `token_id` for `wanted_token_id`. Whoever owns the wanted token can then take the
+    /// trade with `nft_swap`. A new offer for the same token replaces the old one, and an offer is dropped once the
+    /// token changes hands or is burned. Only the token owner can call this and they attach the storage for the offer
+    /// (the excess is refunded, and the rest comes back when the offer is dropped).
+    #[payable]
+    pub fn nft_offer_swap(&mut self, token_id: TokenId, wanted_token_id: TokenId) {
+        let caller = env::predecessor_account_id();
+        require!(
+            self.internal_get_token(&token_id).owner_id == caller,
+            ContractError::Unauthorized.message("Only the token owner can offer it in a swap")
+        );
+        self.internal_get_token(&wanted_token_id);
+        let initial_storage_usage = env::storage_usage();
+
+        self.swap_offers.insert(&token_id, &SwapOffer { owner_id: caller, wanted_token_id });
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// Withdraw the caller's swap offer for their token and get back the storage it paid for
+    pub fn nft_cancel_swap_offer(&mut self, token_id: TokenId) {
+        require!(
+            self.internal_get_token(&token_id).owner_id == env::predecessor_account_id(),
+            ContractError::Unauthorized.message("Only the token owner can cancel its swap offer")
+        );
+        self.internal_remove_swap_offer(&token_id);
+    }
+
+    /// The token `token_id`'s owner offered to trade it for, if they made an offer
+    pub fn swap_offer(&self, token_id: TokenId) -> Option<TokenId> {
+        self.swap_offers.get(&token_id).map(|offer| offer.wanted_token_id)
+    }
+
+    /// Atomically exchange the caller's `my_token_id` for `counterparty`'s `their_token_id`.
+    /// The counterparty must have offered `their_token_id` for exactly `my_token_id` with `nft_offer_swap`, which is
+    /// their consent to this trade and no other. The caller agrees by calling with 1 yoctoNEAR attached.
+    /// Both tokens must belong to transferable series, and both legs are regular transfers, so both owners must be
+    /// on the transfer allowlist.
+    #[payable]
+    pub fn nft_swap(&mut self, my_token_id: TokenId, their_token_id: TokenId, counterparty: AccountId) {
+        //assert that the user attached exactly 1 yoctoNEAR, as for every other transfer
+        assert_one_yocto();
+        let caller = env::predecessor_account_id();
+
+        let my_token = self.internal_get_token(&my_token_id);
+        let their_token = self.internal_get_token(&their_token_id);
+        require!(
+            my_token.owner_id == caller,
+            ContractError::Unauthorized.message("Only the token owner can offer it in a swap")
+        );
+        require!(their_token.owner_id == counterparty, "The counterparty doesn't own the requested token");
+        for token in [&my_token, &their_token] {
+            let series = self.internal_get_series(token.series_id);
+            require!(
+                series.transferable,
+                ContractError::Soulbound.message("Only tokens of transferable series can be swapped")
+            );
+        }
+
+        //the counterparty's offer of their token for this one is their consent to the trade
+        let offer = self.swap_offers.get(&their_token_id);
+        require!(
+            offer.is_some_and(|offer| offer.owner_id == counterparty && offer.wanted_token_id == my_token_id),
+            ContractError::Unauthorized.message("The counterparty hasn't offered their token for this one")
+        );
+
+        //both legs go through the regular transfer so each is checked and emits its nft_transfer event. Moving the
+        //tokens drops both owners' offers and refunds their storage
+        let previous_mine = self.internal_transfer(&caller, &counterparty, &my_token_id, None, None);
+        let previous_theirs = self.internal_transfer(&counterparty, &caller, &their_token_id, None, None);
+
+        //we refund both owners for releasing the storage used up by the approved account IDs
+        refund_approved_account_ids(previous_mine.owner_id, &previous_mine.approved_account_ids);
+        refund_approved_account_ids(previous_theirs.owner_id, &previous_theirs.approved_account_ids);
+    }
+}
+
+impl Contract {
+    //drop the swap offer standing for the token, if any, and give its storage back to whoever made it
+    pub(crate) fn internal_remove_swap_offer(&mut self, token_id: &TokenId) {
+        let initial_storage_usage = env::storage_usage();
+        if let Some(offer) = self.swap_offers.remove(token_id) {
+            let released = initial_storage_usage.saturating_sub(env::storage_usage());
+            if released > 0 {
+                Promise::new(offer.owner_id).transfer(Balance::from(released) * env::storage_byte_cost());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::NonFungibleTokenApproval;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::accounts;
+
+    fn setup_swap() -> (Contract, TokenId, TokenId) {
+        let mut contract = setup_contract();
+        for series_id in [1, 2, 3] {
+            create_sample_series(&mut contract, series_id);
+            contract.set_series_transferable(series_id, true);
+        }
+        contract.set_allowed_addresses(vec![accounts(1), accounts(2)]);
+        let alice_token = mint_to(&mut contract, 1, accounts(1));
+        let bob_token = mint_to(&mut contract, 2, accounts(2));
+        (contract, alice_token, bob_token)
+    }
+
+    fn offer(contract: &mut Contract, owner_id: AccountId, token_id: &TokenId, wanted_token_id: &TokenId) {
+        set_context(owner_id, STORAGE_DEPOSIT);
+        contract.nft_offer_swap(token_id.clone(), wanted_token_id.clone());
+    }
+
+    #[test]
+    fn swap_exchanges_both_tokens() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        offer(&mut contract, accounts(2), &bob_token, &alice_token);
+        assert_eq!(contract.swap_offer(bob_token.clone()), Some(alice_token.clone()));
+
+        set_context(accounts(1), 1);
+        contract.nft_swap(alice_token.clone(), bob_token.clone(), accounts(2));
+
+        assert_eq!(contract.tokens_by_id.get(&alice_token).unwrap().owner_id, accounts(2));
+        assert_eq!(contract.tokens_by_id.get(&bob_token).unwrap().owner_id, accounts(1));
+        assert!(contract.swap_offer(bob_token).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "The counterparty hasn't offered their token for this one")]
+    fn approving_the_contract_is_not_consent_to_a_swap() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        contract.nft_approve(bob_token.clone(), "badges.near".parse().unwrap(), None);
+
+        set_context(accounts(1), 1);
+        contract.nft_swap(alice_token, bob_token, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "The counterparty hasn't offered their token for this one")]
+    fn an_offer_can_only_be_taken_with_the_wanted_token() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        offer(&mut contract, accounts(2), &bob_token, &alice_token);
+        //a worthless token from another transferable series isn't what was asked for
+        let other_token = mint_to(&mut contract, 3, accounts(1));
+
+        set_context(accounts(1), 1);
+        contract.nft_swap(other_token, bob_token, accounts(2));
+    }
+
+    #[test]
+    fn an_offer_is_dropped_once_the_token_changes_hands() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        offer(&mut contract, accounts(2), &bob_token, &alice_token);
+
+        set_context(accounts(2), 1);
+        contract.nft_transfer(accounts(1), bob_token.clone(), None, None);
+        assert!(transfers_to(&accounts(2)).iter().sum::<Balance>() > 0);
+
+        //the offer doesn't come back with the token
+        set_context(accounts(1), 1);
+        contract.nft_transfer(accounts(2), bob_token.clone(), None, None);
+        assert!(contract.swap_offer(bob_token).is_none());
+    }
+
+    #[test]
+    fn cancelling_an_offer_refunds_its_storage() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        set_context(accounts(2), STORAGE_DEPOSIT);
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_offer_swap(bob_token.clone(), alice_token);
+        let offer_cost = Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+
+        set_context(accounts(2), 0);
+        contract.nft_cancel_swap_offer(bob_token.clone());
+
+        assert!(contract.swap_offer(bob_token).is_none());
+        assert_eq!(transfers_to(&accounts(2)), vec![offer_cost]);
+    }
+
+    #[test]
+    fn burning_a_token_drops_its_offer() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        offer(&mut contract, accounts(2), &bob_token, &alice_token);
+
+        set_context(owner(), 1);
+        contract.nft_revoke_badge(bob_token.clone(), RevokeReason::Fraud, None);
+
+        assert!(contract.swap_offers.get(&bob_token).is_none());
+    }
+
+    #[test]
+    fn the_transferable_flag_only_gates_swaps() {
+        let (mut contract, _, bob_token) = setup_swap();
+        set_context(owner(), 0);
+        contract.set_series_transferable(2, false);
+
+        set_context(accounts(2), 1);
+        contract.nft_transfer(accounts(1), bob_token.clone(), None, None);
+
+        assert_eq!(contract.tokens_by_id.get(&bob_token).unwrap().owner_id, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only tokens of transferable series can be swapped")]
+    fn soulbound_tokens_cannot_be_swapped() {
+        let (mut contract, alice_token, bob_token) = setup_swap();
+        set_context(owner(), 0);
+        contract.set_series_transferable(2, false);
+        offer(&mut contract, accounts(2), &bob_token, &alice_token);
+
+        set_context(accounts(1), 1);
+        contract.nft_swap(alice_token, bob_token, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfer not allowed to this address")]
+    fn swaps_only_go_to_allowlisted_accounts() {
+        let (mut contract, alice_token, _) = setup_swap();
+        let carol_token = mint_to(&mut contract, 2, accounts(3));
+        offer(&mut contract, accounts(3), &carol_token, &alice_token);
+
+        set_context(accounts(1), 1);
+        contract.nft_swap(alice_token, carol_token, accounts(3));
+    }
+}
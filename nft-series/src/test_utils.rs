@@ -0,0 +1,118 @@
+use crate::*;
+use near_sdk::mock::VmAction;
+use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+use near_sdk::json_types::U64;
+use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+/// Deposit attached to storage-paying calls in tests (1 NEAR is always enough).
+pub(crate) const STORAGE_DEPOSIT: Balance = 1_000_000_000_000_000_000_000_000;
+
+/// The account that deploys and owns the contract in tests.
+pub(crate) fn owner() -> AccountId {
+    accounts(0)
+}
+
+/// Set up the mocked blockchain with the given caller and attached deposit.
+pub(crate) fn set_context(predecessor: AccountId, deposit: Balance) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id("badges.near".parse().unwrap())
+        .signer_account_id(predecessor.clone())
+        .predecessor_account_id(predecessor)
+        .attached_deposit(deposit);
+    testing_env!(builder.build());
+    builder
+}
+
+/// Set up the mocked blockchain as a callback on this contract that receives the given promise result.
+pub(crate) fn set_callback_context(result: PromiseResult) {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id("badges.near".parse().unwrap())
+        .predecessor_account_id("badges.near".parse().unwrap());
+    testing_env!(
+        builder.build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![result],
+    );
+}
+
+/// Initialize a fresh contract owned by `owner()`.
+pub(crate) fn setup_contract() -> Contract {
+    set_context(owner(), 0);
+    Contract::new_default_meta(owner())
+}
+
+/// Series metadata with only a title set.
+pub(crate) fn sample_metadata(title: &str) -> TokenMetadata {
+    TokenMetadata {
+        title: Some(title.to_string()),
+        description: None,
+        media: None,
+        media_hash: None,
+        animation_url: None,
+        copies: None,
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+        achievement_type: None,
+        achievement_level: None,
+        activity_name: None,
+        completion_date: None,
+        community_event: None,
+        external_links: None,
+        creator: None,
+    }
+}
+
+/// A spec for series `id` with the given metadata, leaving everything else to the defaults.
+pub(crate) fn series_spec(id: SeriesId, metadata: TokenMetadata) -> SeriesSpec {
+    SeriesSpec {
+        id,
+        metadata,
+        royalty: None,
+        price: None,
+        description: None,
+        external_url: None,
+        visible: None,
+        collection_media: None,
+        transferable: None,
+        unique_per_account: None,
+        claim_only: None,
+        public_mint_enabled: None,
+        claim_deadline: None,
+        on_mint_callback: None,
+        storage_payer: None,
+    }
+}
+
+/// Create a free series owned by the contract owner.
+pub(crate) fn create_sample_series(contract: &mut Contract, id: SeriesId) -> SeriesId {
+    set_context(owner(), STORAGE_DEPOSIT);
+    contract.create_series(id, sample_metadata("Badge"), None, None, None, None, None, None, None)
+}
+
+/// Mint a token of the given series to `receiver_id` as the contract owner.
+pub(crate) fn mint_to(contract: &mut Contract, series_id: SeriesId, receiver_id: AccountId) -> TokenId {
+    set_context(owner(), STORAGE_DEPOSIT);
+    contract.nft_mint(U64(series_id), receiver_id, None, None, None, None)
+}
+
+/// The amounts of every NEAR transfer to `account_id` created in the current context.
+pub(crate) fn transfers_to(account_id: &AccountId) -> Vec<Balance> {
+    get_created_receipts()
+        .into_iter()
+        .filter(|receipt| &receipt.receiver_id == account_id)
+        .flat_map(|receipt| receipt.actions)
+        .filter_map(|action| match action {
+            VmAction::Transfer { deposit } => Some(deposit),
+            _ => None,
+        })
+        .collect()
+}